@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::write::GzEncoder;
+
+use crate::report::Report;
+
+const BLOCK: usize = 512;
+/// The largest size the 11 octal digits of a ustar header can hold, 8 GiB less a byte
+const MAX_SIZE: u64 = 0o77777777777;
+
+/// Write a `.tar.gz` with the rpms of the report, a `SHA256SUMS` file and the report itself.
+/// The rpms are stored by file name, and under the name of their directory too when the same
+/// file name was written more than once, like the copies signed with each key
+pub fn write_bundle(path: &Path, report: &Report) -> Result<(), Box<dyn Error>> {
+    let mut counts = HashMap::new();
+    for (rpm, _) in report.checksums() {
+        *counts.entry(rpm.file_name()).or_insert(0) += 1;
+    }
+
+    let mut entries = Vec::new();
+    let mut sums = String::new();
+    for (rpm, sha256) in report.checksums() {
+        let file_name = rpm.file_name().ok_or("invalid rpm path")?;
+        let name = match rpm.parent().and_then(Path::file_name) {
+            Some(dir) if counts[&Some(file_name)] > 1 => Path::new(dir).join(file_name),
+            _ => file_name.into(),
+        };
+        let name = name.to_string_lossy().into_owned();
+
+        sums.push_str(&format!("{sha256}  {name}\n"));
+        entries.push((name, fs::read(rpm)?));
+    }
+
+    entries.push(("SHA256SUMS".to_owned(), sums.into_bytes()));
+    entries.push(("report.json".to_owned(), report.to_json()?.into_bytes()));
+
+    let mtime = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut encoder = GzEncoder::new(fs::File::create(path)?, flate2::Compression::default());
+    for (name, data) in &entries {
+        encoder.write_all(&header(name, data.len() as u64, mtime)?)?;
+        encoder.write_all(data)?;
+        encoder.write_all(&vec![0; data.len().next_multiple_of(BLOCK) - data.len()])?;
+    }
+
+    // the archive ends with two empty blocks
+    encoder.write_all(&[0; 2 * BLOCK])?;
+    encoder.finish()?;
+
+    Ok(())
+}
+
+/// A ustar header for a regular file
+fn header(name: &str, size: u64, mtime: u64) -> Result<[u8; BLOCK], String> {
+    if name.len() >= 100 {
+        return Err(format!("{name} is too long to bundle"));
+    }
+
+    if size > MAX_SIZE {
+        return Err(format!("{name} is too large to bundle, it's 8 GiB or more"));
+    }
+
+    let mut header = [0; BLOCK];
+    let mut field = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+
+    field(0, name.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{size:011o}\0").as_bytes());
+    field(136, format!("{mtime:011o}\0").as_bytes());
+    // the checksum is computed with its own field filled with spaces
+    field(148, b"        ");
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+    field(265, b"root");
+    field(297, b"root");
+
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    /// The value of an octal header field
+    fn octal(field: &[u8]) -> u64 {
+        let digits = std::str::from_utf8(field).unwrap();
+        u64::from_str_radix(digits.trim_matches(['\0', ' ']), 8).unwrap()
+    }
+
+    #[test]
+    fn sizes() {
+        assert!(header("a.rpm", MAX_SIZE, 0).is_ok());
+        assert!(header("a.rpm", MAX_SIZE + 1, 0).is_err());
+        assert!(header(&"a".repeat(100), 0, 0).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir =
+            std::env::temp_dir().join(format!("cargo-make-rpm-bundle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rpm_path = dir.join("a-1.0-1.noarch.rpm");
+        rpm::PackageBuilder::new("a", "1.0", "MIT", "noarch", "A package")
+            .build()
+            .unwrap()
+            .write_file(&rpm_path)
+            .unwrap();
+        let report = Report::new(std::slice::from_ref(&rpm_path)).unwrap();
+        let bundle = dir.join("bundle.tar.gz");
+        write_bundle(&bundle, &report).unwrap();
+
+        let mut tar = Vec::new();
+        GzDecoder::new(fs::File::open(&bundle).unwrap())
+            .read_to_end(&mut tar)
+            .unwrap();
+
+        let mut entries = Vec::new();
+        let mut blocks = tar.chunks(BLOCK);
+        while let Some(header) = blocks.next() {
+            if header.iter().all(|&b| b == 0) {
+                break;
+            }
+
+            let mut checksum = header.to_vec();
+            checksum[148..156].fill(b' ');
+            let sum: u64 = checksum.iter().map(|&b| u64::from(b)).sum();
+            assert_eq!(octal(&header[148..156]), sum);
+            assert_eq!(&header[257..263], b"ustar\0");
+
+            let name = std::str::from_utf8(&header[..100]).unwrap();
+            let name = name.trim_end_matches('\0').to_owned();
+            let size = octal(&header[124..136]) as usize;
+            let mut data = Vec::new();
+            for _ in 0..size.div_ceil(BLOCK) {
+                data.extend(blocks.next().unwrap());
+            }
+            data.truncate(size);
+            entries.push((name, data));
+        }
+
+        let names: Vec<&str> = entries.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["a-1.0-1.noarch.rpm", "SHA256SUMS", "report.json"]);
+        assert_eq!(entries[0].1, fs::read(&rpm_path).unwrap());
+        let (_, sha256) = report.checksums().next().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&entries[1].1),
+            format!("{sha256}  a-1.0-1.noarch.rpm\n")
+        );
+        assert_eq!(entries[2].1, report.to_json().unwrap().into_bytes());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
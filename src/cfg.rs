@@ -0,0 +1,231 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::Triplet;
+
+/// A `cfg(...)` predicate, as used in cargo's `[target.'cfg(...)']` tables
+#[derive(Debug, PartialEq)]
+pub enum Cfg {
+    Triple(String),
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl Cfg {
+    /// Parse either `cfg(<predicate>)` or a plain target triple, which matches only that target
+    pub fn parse_target(s: &str) -> Result<Cfg, String> {
+        let s = s.trim();
+        let Some(predicate) = s.strip_prefix("cfg(").and_then(|s| s.strip_suffix(')')) else {
+            return Ok(Cfg::Triple(s.to_owned()));
+        };
+
+        let mut chars = predicate.chars().peekable();
+        let cfg = parse_predicate(&mut chars).map_err(|e| format!("invalid cfg {s}: {e}"))?;
+        skip_whitespace(&mut chars);
+        if let Some(c) = chars.next() {
+            return Err(format!("invalid cfg {s}: unexpected {c}"));
+        }
+
+        Ok(cfg)
+    }
+
+    /// Whether the predicate holds for the target
+    pub fn matches(&self, triplet: &Triplet) -> bool {
+        match self {
+            Cfg::Triple(triple) => triplet.to_string() == *triple,
+            Cfg::Name(name) => triplet.families().contains(&name.as_str()),
+            Cfg::KeyValue(key, value) => match key.as_str() {
+                "target_arch" => triplet.cfg_arch() == value,
+                "target_os" => triplet.os == *value,
+                "target_vendor" => triplet.vendor == *value,
+                "target_env" => triplet.env() == value,
+                "target_family" => triplet.families().contains(&value.as_str()),
+                "target_pointer_width" => triplet.pointer_width() == value,
+                "target_endian" => triplet.endian() == value,
+                // the keys are checked when parsing
+                _ => unreachable!("unknown cfg key {key}"),
+            },
+            Cfg::All(cfgs) => cfgs.iter().all(|c| c.matches(triplet)),
+            Cfg::Any(cfgs) => cfgs.iter().any(|c| c.matches(triplet)),
+            Cfg::Not(cfg) => !cfg.matches(triplet),
+        }
+    }
+}
+
+/// The names and keys a predicate can use, the others don't describe the target or need more
+/// than the triple to evaluate
+const NAMES: &[&str] = &["unix", "windows", "wasm"];
+const KEYS: &[&str] = &[
+    "target_arch",
+    "target_os",
+    "target_vendor",
+    "target_env",
+    "target_family",
+    "target_pointer_width",
+    "target_endian",
+];
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_ident(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    skip_whitespace(chars);
+    let mut ident = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+        ident.push(c);
+    }
+
+    if ident.is_empty() {
+        return Err("expected an identifier".to_owned());
+    }
+
+    Ok(ident)
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    skip_whitespace(chars);
+    if chars.next() != Some('"') {
+        return Err("expected a string".to_owned());
+    }
+
+    let mut string = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            return Ok(string);
+        }
+        string.push(c);
+    }
+
+    Err("unterminated string".to_owned())
+}
+
+fn parse_list(chars: &mut Peekable<Chars>) -> Result<Vec<Cfg>, String> {
+    let mut cfgs = Vec::new();
+    loop {
+        skip_whitespace(chars);
+        if chars.next_if_eq(&')').is_some() {
+            return Ok(cfgs);
+        }
+
+        cfgs.push(parse_predicate(chars)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some(')') => return Ok(cfgs),
+            _ => return Err("expected , or )".to_owned()),
+        }
+    }
+}
+
+fn parse_predicate(chars: &mut Peekable<Chars>) -> Result<Cfg, String> {
+    let ident = parse_ident(chars)?;
+    skip_whitespace(chars);
+
+    if chars.next_if_eq(&'(').is_some() {
+        return match ident.as_str() {
+            "all" => Ok(Cfg::All(parse_list(chars)?)),
+            "any" => Ok(Cfg::Any(parse_list(chars)?)),
+            "not" => {
+                let mut cfgs = parse_list(chars)?;
+                if cfgs.len() != 1 {
+                    return Err("not() takes exactly one predicate".to_owned());
+                }
+                Ok(Cfg::Not(Box::new(cfgs.remove(0))))
+            }
+            other => Err(format!("unknown operator {other}")),
+        };
+    }
+
+    if chars.next_if_eq(&'=').is_some() {
+        if !KEYS.contains(&ident.as_str()) {
+            return Err(format!(
+                "unsupported key {ident}, expected one of {}",
+                KEYS.join(", ")
+            ));
+        }
+        return Ok(Cfg::KeyValue(ident, parse_string(chars)?));
+    }
+
+    if !NAMES.contains(&ident.as_str()) {
+        return Err(format!(
+            "unsupported name {ident}, expected one of {}",
+            NAMES.join(", ")
+        ));
+    }
+
+    Ok(Cfg::Name(ident))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn matches(cfg: &str, triple: &str) -> bool {
+        Cfg::parse_target(cfg)
+            .unwrap()
+            .matches(&Triplet::from_str(triple).unwrap())
+    }
+
+    #[test]
+    fn target_env() {
+        let cfg = r#"cfg(target_env = "musl")"#;
+        assert!(matches(cfg, "x86_64-unknown-linux-musl"));
+        assert!(!matches(cfg, "x86_64-unknown-linux-gnu"));
+
+        let cfg = r#"cfg(all(target_os = "linux", not(target_env = "musl")))"#;
+        assert!(matches(cfg, "aarch64-unknown-linux-gnu"));
+        assert!(!matches(cfg, "aarch64-unknown-linux-musl"));
+    }
+
+    #[test]
+    fn target_properties() {
+        assert!(matches("cfg(unix)", "x86_64-unknown-linux-gnu"));
+        assert!(!matches("cfg(windows)", "x86_64-unknown-linux-gnu"));
+        assert!(matches(
+            r#"cfg(target_family = "unix")"#,
+            "riscv64gc-unknown-linux-gnu"
+        ));
+
+        let cfg = r#"cfg(target_pointer_width = "64")"#;
+        assert!(matches(cfg, "x86_64-unknown-linux-gnu"));
+        assert!(!matches(cfg, "x86_64-unknown-linux-gnux32"));
+        assert!(!matches(cfg, "i686-unknown-linux-gnu"));
+
+        let cfg = r#"cfg(target_endian = "big")"#;
+        assert!(matches(cfg, "s390x-unknown-linux-gnu"));
+        assert!(matches(cfg, "powerpc64-unknown-linux-gnu"));
+        assert!(!matches(cfg, "powerpc64le-unknown-linux-gnu"));
+        assert!(!matches(cfg, "aarch64-unknown-linux-gnu"));
+
+        assert!(matches(
+            r#"cfg(any(target_arch = "x86", target_arch = "x86_64"))"#,
+            "i686-unknown-linux-musl"
+        ));
+    }
+
+    #[test]
+    fn triples() {
+        assert!(matches(
+            "x86_64-unknown-linux-gnu",
+            "x86_64-unknown-linux-gnu"
+        ));
+        assert!(!matches(
+            "x86_64-unknown-linux-gnu",
+            "x86_64-unknown-linux-musl"
+        ));
+    }
+
+    #[test]
+    fn unsupported_predicates() {
+        assert!(Cfg::parse_target("cfg(linux)").is_err());
+        assert!(Cfg::parse_target(r#"cfg(feature = "tls")"#).is_err());
+        assert!(Cfg::parse_target("cfg(nope(unix))").is_err());
+        assert!(Cfg::parse_target("cfg(not(unix, windows))").is_err());
+        assert!(Cfg::parse_target(r#"cfg(target_os = "linux)"#).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+use rpm::{Dependency, DependencyFlags, FileMode, PackageMetadata};
+
+/// What an rpm contains, keyed by header name, dependency, scriptlet or file path. Keys are
+/// ordered by their section first, so each section is shown together and headers keep their order
+type Contents = BTreeMap<(usize, String), String>;
+
+const DEPENDENCIES: usize = 100;
+const SCRIPTLETS: usize = 200;
+const FILES: usize = 300;
+
+/// The differences between two rpms for `--diff`, as `-` and `+` lines, empty if they're the same
+pub fn diff(old: &Path, new: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let old = contents(&rpm::Package::open(old)?.metadata);
+    let new = contents(&rpm::Package::open(new)?.metadata);
+
+    let mut lines = Vec::new();
+    let mut keys: Vec<&(usize, String)> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        match (old.get(key), new.get(key)) {
+            (Some(old), Some(new)) if old == new => {}
+            (old, new) => {
+                lines.extend(old.map(|value| line('-', &key.1, value)));
+                lines.extend(new.map(|value| line('+', &key.1, value)));
+            }
+        }
+    }
+
+    Ok(lines)
+}
+
+fn line(sign: char, key: &str, value: &str) -> String {
+    if value.is_empty() {
+        format!("{sign}{key}")
+    } else if value.contains('\n') {
+        // scriptlets and descriptions are indented under their key
+        let value = value
+            .lines()
+            .collect::<Vec<_>>()
+            .join(&format!("\n{sign}    "));
+        format!("{sign}{key}:\n{sign}    {value}")
+    } else {
+        format!("{sign}{key}: {value}")
+    }
+}
+
+fn contents(metadata: &PackageMetadata) -> Contents {
+    let mut contents = Contents::new();
+    let headers = [
+        ("Name", metadata.get_name().map(str::to_owned)),
+        ("Epoch", metadata.get_epoch().map(|e| e.to_string())),
+        ("Version", metadata.get_version().map(str::to_owned)),
+        ("Release", metadata.get_release().map(str::to_owned)),
+        ("Arch", metadata.get_arch().map(str::to_owned)),
+        ("Summary", metadata.get_summary().map(str::to_owned)),
+        ("Description", metadata.get_description().map(str::to_owned)),
+        ("License", metadata.get_license().map(str::to_owned)),
+        ("URL", metadata.get_url().map(str::to_owned)),
+        ("VCS", metadata.get_vcs().map(str::to_owned)),
+        ("Vendor", metadata.get_vendor().map(str::to_owned)),
+        ("Group", metadata.get_group().map(str::to_owned)),
+        ("Packager", metadata.get_packager().map(str::to_owned)),
+        (
+            "BuildTime",
+            metadata.get_build_time().map(|t| t.to_string()),
+        ),
+        ("BuildHost", metadata.get_build_host().map(str::to_owned)),
+        ("SourceRPM", metadata.get_source_rpm().map(str::to_owned)),
+        (
+            "InstalledSize",
+            metadata.get_installed_size().map(|s| s.to_string()),
+        ),
+        (
+            "PayloadCompressor",
+            metadata.get_payload_compressor().map(|c| format!("{c:?}")),
+        ),
+    ];
+    for (i, (name, value)) in headers.into_iter().enumerate() {
+        // missing optional headers are compared as empty
+        contents.insert((i, name.to_owned()), value.unwrap_or_default());
+    }
+
+    let dependencies = [
+        ("Provides", metadata.get_provides()),
+        ("Requires", metadata.get_requires()),
+        ("Conflicts", metadata.get_conflicts()),
+        ("Obsoletes", metadata.get_obsoletes()),
+        ("Recommends", metadata.get_recommends()),
+        ("Suggests", metadata.get_suggests()),
+        ("Enhances", metadata.get_enhances()),
+        ("Supplements", metadata.get_supplements()),
+    ];
+    for (kind, dependencies) in dependencies {
+        for dependency in dependencies.unwrap_or_default() {
+            let key = format!("{kind}: {}", dependency_string(&dependency));
+            contents.insert((DEPENDENCIES, key), String::new());
+        }
+    }
+
+    let scriptlets = [
+        ("%pre", metadata.get_pre_install_script()),
+        ("%post", metadata.get_post_install_script()),
+        ("%preun", metadata.get_pre_uninstall_script()),
+        ("%postun", metadata.get_post_uninstall_script()),
+        ("%pretrans", metadata.get_pre_trans_script()),
+        ("%posttrans", metadata.get_post_trans_script()),
+        ("%preuntrans", metadata.get_pre_untrans_script()),
+        ("%postuntrans", metadata.get_post_untrans_script()),
+    ];
+    for (kind, scriptlet) in scriptlets {
+        let Ok(scriptlet) = scriptlet else { continue };
+        let key = match scriptlet.program {
+            Some(program) => format!("{kind} -p {}", program.join(" ")),
+            None => kind.to_owned(),
+        };
+        // a trailing newline would make a one line script look multi-line
+        contents.insert(
+            (SCRIPTLETS, key),
+            format!("{}\n", scriptlet.script.trim_end()),
+        );
+    }
+
+    for entry in metadata.get_file_entries().unwrap_or_default() {
+        let kind = match entry.mode {
+            FileMode::Dir { .. } => "dir",
+            FileMode::SymbolicLink { .. } => "symlink",
+            _ => "file",
+        };
+
+        let mut attributes = vec![
+            kind.to_owned(),
+            format!("{:04o}", entry.mode.permissions()),
+            format!("{}:{}", entry.ownership.user, entry.ownership.group),
+            format!("mtime {}", entry.modified_at.0),
+        ];
+        if kind == "file" {
+            attributes.push(format!("{} bytes", entry.size));
+            attributes.extend(entry.digest.map(|d| format!("digest {d}")));
+        }
+        if !entry.linkto.is_empty() {
+            attributes.push(format!("-> {}", entry.linkto));
+        }
+        attributes.extend(entry.caps.map(|c| format!("caps {c}")));
+        attributes.extend(entry.flags.iter_names().map(|(f, _)| f.to_lowercase()));
+
+        let key = entry.path.display().to_string();
+        contents.insert((FILES, key), attributes.join(", "));
+    }
+
+    contents
+}
+
+/// A dependency the way it's written in a spec file, e.g. `foo >= 1.0`
+fn dependency_string(dependency: &Dependency) -> String {
+    let flags = dependency.flags;
+    let operator = if flags.contains(DependencyFlags::LE) {
+        "<="
+    } else if flags.contains(DependencyFlags::GE) {
+        ">="
+    } else if flags.contains(DependencyFlags::LESS) {
+        "<"
+    } else if flags.contains(DependencyFlags::GREATER) {
+        ">"
+    } else if flags.contains(DependencyFlags::EQUAL) {
+        "="
+    } else {
+        return dependency.name.clone();
+    };
+
+    format!("{} {operator} {}", dependency.name, dependency.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use rpm::FileOptions;
+
+    use super::*;
+
+    /// Write a package `a` with a config file of `mode`, a requirement on at least version
+    /// `bash` of bash and a `postinstall` script, dated so two builds only differ in those
+    fn write(dir: &Path, name: &str, mode: u16, bash: &str, postinstall: &str) -> PathBuf {
+        let source = dir.join("a.conf");
+        fs::write(&source, "a=1\n").unwrap();
+        let path = dir.join(name);
+        rpm::PackageBuilder::new("a", "1.0", "MIT", "noarch", "A package")
+            .source_date(1_600_000_000)
+            .build_host("builder")
+            .requires(Dependency::greater_eq("bash", bash))
+            .post_install_script(postinstall)
+            .with_file(
+                &source,
+                FileOptions::new("/etc/a.conf").mode(0o100000 | mode),
+            )
+            .unwrap()
+            .build()
+            .unwrap()
+            .write_file(&path)
+            .unwrap();
+        path
+    }
+
+    /// A scratch directory for a test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("cargo-make-rpm-diff-{test}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn differences() {
+        let dir = TempDir::new("differences");
+        let old = write(&dir.0, "old.rpm", 0o644, "5.0", "echo a");
+        let new = write(&dir.0, "new.rpm", 0o600, "5.1", "echo a\necho b");
+
+        let file = |sign: char, mode: &str| {
+            format!(
+                "{sign}/etc/a.conf: file, {mode}, root:root, mtime 1600000000, 4 bytes, digest \
+                 fe3209d6d4f51935b391288a43df48d9ddece1a992597ae53387ca16611a9179"
+            )
+        };
+        assert_eq!(
+            diff(&old, &new).unwrap(),
+            [
+                "-Requires: bash >= 5.0".to_owned(),
+                "+Requires: bash >= 5.1".to_owned(),
+                "-%post:\n-    echo a".to_owned(),
+                "+%post:\n+    echo a\n+    echo b".to_owned(),
+                file('-', "0644"),
+                file('+', "0600"),
+            ]
+        );
+    }
+
+    #[test]
+    fn identical() {
+        let dir = TempDir::new("identical");
+        let old = write(&dir.0, "old.rpm", 0o644, "5.0", "echo a");
+        let new = write(&dir.0, "new.rpm", 0o644, "5.0", "echo a");
+        assert!(diff(&old, &new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn dependency_operators() {
+        let dependencies = [
+            (Dependency::any("a"), "a"),
+            (Dependency::eq("a", "1"), "a = 1"),
+            (Dependency::less("a", "1"), "a < 1"),
+            (Dependency::less_eq("a", "1"), "a <= 1"),
+            (Dependency::greater("a", "1"), "a > 1"),
+            (Dependency::greater_eq("a", "1"), "a >= 1"),
+        ];
+        for (dependency, expected) in dependencies {
+            assert_eq!(dependency_string(&dependency), expected);
+        }
+    }
+}
@@ -0,0 +1,457 @@
+/// Section type of the dynamic linking table
+const SHT_DYNAMIC: u32 = 6;
+/// Dynamic entry naming a shared library the file needs
+const DT_NEEDED: u64 = 1;
+/// Dynamic entry with the name a shared library is linked as
+const DT_SONAME: u64 = 14;
+
+/// ELF machines and the rustc `target_arch` they are built for
+const MACHINES: &[(u16, &str)] = &[
+    (2, "sparc"),
+    (3, "x86"),
+    (8, "mips"),
+    (8, "mips64"),
+    (8, "mips32r6"),
+    (8, "mips64r6"),
+    (20, "powerpc"),
+    (21, "powerpc64"),
+    (22, "s390x"),
+    (40, "arm"),
+    (43, "sparc64"),
+    (62, "x86_64"),
+    (183, "aarch64"),
+    (243, "riscv32"),
+    (243, "riscv64"),
+    (258, "loongarch64"),
+];
+
+/// The ELF machine of binaries built for a rustc `target_arch`
+pub fn arch_machine(arch: &str) -> Option<u16> {
+    MACHINES.iter().find(|(_, a)| *a == arch).map(|(m, _)| *m)
+}
+
+/// The ELF machine of binaries in packages for an rpm arch
+pub fn rpm_arch_machine(arch: &str) -> Option<u16> {
+    let arch = match arch {
+        "x86_64" | "x86_64_v2" | "x86_64_v3" | "x86_64_v4" | "amd64" => "x86_64",
+        "i386" | "i486" | "i586" | "i686" | "athlon" => "x86",
+        "ppc" => "powerpc",
+        "ppc64" | "ppc64le" | "ppc64p7" => "powerpc64",
+        "s390" => "s390x",
+        "sparcv9" => return Some(18),
+        arch if arch.starts_with("arm") => "arm",
+        arch if arch.starts_with("mips") => "mips",
+        arch => arch,
+    };
+
+    arch_machine(arch)
+}
+
+/// A readable name for an ELF machine
+pub fn machine_name(machine: u16) -> String {
+    match MACHINES.iter().find(|(m, _)| *m == machine) {
+        Some((_, arch)) => arch.to_string(),
+        None => format!("machine {machine}"),
+    }
+}
+
+/// The parts of an ELF file needed to inspect packaged binaries
+pub struct Elf<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    is_le: bool,
+    pub machine: u16,
+    sections: Vec<Section>,
+}
+
+pub struct Section {
+    pub name: String,
+    kind: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+impl<'a> Elf<'a> {
+    /// Parse the ELF header and section table, returns `None` if the data isn't an ELF file and
+    /// an error if it's a truncated or malformed one
+    pub fn parse(data: &'a [u8]) -> Result<Option<Elf<'a>>, String> {
+        if data.get(..4) != Some(b"\x7fELF") {
+            return Ok(None);
+        }
+
+        let mut elf = Elf {
+            data,
+            is_64: data.get(4) == Some(&2),
+            is_le: data.get(5) == Some(&1),
+            machine: 0,
+            sections: Vec::new(),
+        };
+
+        elf.machine = elf.u16(0x12)?;
+
+        let (shoff, shentsize, shnum, shstrndx) = if elf.is_64 {
+            (
+                elf.u64(0x28)?,
+                elf.u16(0x3a)?,
+                elf.u16(0x3c)?,
+                elf.u16(0x3e)?,
+            )
+        } else {
+            (
+                elf.u32(0x20)?.into(),
+                elf.u16(0x2e)?,
+                elf.u16(0x30)?,
+                elf.u16(0x32)?,
+            )
+        };
+
+        // sh_name is an offset in the section name table, which is read once all headers are
+        let mut names = Vec::with_capacity(shnum.into());
+        for i in 0..u64::from(shnum) {
+            let header = offset(shoff, i.checked_mul(shentsize.into()))?;
+            let field = |at| offset(header, Some(at));
+            let (offset, size, link) = if elf.is_64 {
+                (
+                    elf.u64(field(24)?)?,
+                    elf.u64(field(32)?)?,
+                    elf.u32(field(40)?)?,
+                )
+            } else {
+                (
+                    elf.u32(field(16)?)?.into(),
+                    elf.u32(field(20)?)?.into(),
+                    elf.u32(field(24)?)?,
+                )
+            };
+
+            names.push(elf.u32(header)?);
+            elf.sections.push(Section {
+                name: String::new(),
+                kind: elf.u32(field(4)?)?,
+                offset,
+                size,
+                link,
+            });
+        }
+
+        // section 0 is never a string table, files without section names leave the index at 0
+        if shstrndx != 0 {
+            for (i, name) in names.into_iter().enumerate() {
+                elf.sections[i].name = elf.string(shstrndx.into(), name.into())?;
+            }
+        }
+
+        Ok(Some(elf))
+    }
+
+    /// Whether this is a 64-bit ELF file
+    pub fn is_64(&self) -> bool {
+        self.is_64
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// The sonames of the shared libraries the file needs, from its `DT_NEEDED` entries
+    pub fn needed(&self) -> Result<Vec<String>, String> {
+        self.dynamic_strings(DT_NEEDED)
+    }
+
+    /// The soname of a shared library, from its `DT_SONAME` entry
+    pub fn soname(&self) -> Result<Option<String>, String> {
+        Ok(self.dynamic_strings(DT_SONAME)?.into_iter().next())
+    }
+
+    /// The string values of the dynamic entries tagged `tag`
+    fn dynamic_strings(&self, tag: u64) -> Result<Vec<String>, String> {
+        let Some(dynamic) = self.sections.iter().find(|s| s.kind == SHT_DYNAMIC) else {
+            return Ok(Vec::new());
+        };
+
+        let entry_size = if self.is_64 { 16 } else { 8 };
+        let mut strings = Vec::new();
+        for i in 0..dynamic.size / entry_size {
+            let entry = offset(dynamic.offset, i.checked_mul(entry_size))?;
+            let (t, value) = if self.is_64 {
+                (self.u64(entry)?, self.u64(offset(entry, Some(8))?)?)
+            } else {
+                (
+                    self.u32(entry)?.into(),
+                    self.u32(offset(entry, Some(4))?)?.into(),
+                )
+            };
+
+            match t {
+                // DT_NULL ends the table
+                0 => break,
+                t if t == tag => strings.push(self.string(dynamic.link.into(), value)?),
+                _ => {}
+            }
+        }
+
+        Ok(strings)
+    }
+
+    /// The null terminated string at `index` in the string table section `table`
+    fn string(&self, table: u64, index: u64) -> Result<String, String> {
+        let table = usize::try_from(table)
+            .ok()
+            .and_then(|table| self.sections.get(table))
+            .ok_or("string table index out of range")?;
+        if index >= table.size {
+            return Err("string index out of range".to_owned());
+        }
+
+        let start = offset(table.offset, Some(index))?;
+        let end = offset(table.offset, Some(table.size))?;
+        let strings = self.slice(start, end - start)?;
+        let end = strings
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or("unterminated string")?;
+
+        Ok(String::from_utf8_lossy(&strings[..end]).into_owned())
+    }
+
+    /// The `len` bytes at `offset`, an error if they're past the end of the file
+    fn slice(&self, offset: u64, len: u64) -> Result<&'a [u8], String> {
+        let end = offset.checked_add(len).ok_or("offset out of range")?;
+        usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(end).ok())
+            .and_then(|(start, end)| self.data.get(start..end))
+            .ok_or_else(|| "truncated ELF file".to_owned())
+    }
+
+    fn bytes<const N: usize>(&self, offset: u64) -> Result<[u8; N], String> {
+        Ok(self.slice(offset, N as u64)?.try_into().unwrap())
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, String> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.is_le {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, String> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.is_le {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64, String> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.is_le {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+}
+
+/// `base + add`, where `add` is `None` when computing it overflowed, an error for offsets past
+/// what a file can hold
+fn offset(base: u64, add: Option<u64>) -> Result<u64, String> {
+    add.and_then(|add| base.checked_add(add))
+        .ok_or_else(|| "offset out of range".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The section types of the fixtures
+    const SHT_STRTAB: u32 = 3;
+    const SHT_NOTE: u32 = 7;
+
+    /// Bytes written in the byte order of a fixture
+    struct Writer {
+        is_le: bool,
+        data: Vec<u8>,
+    }
+
+    impl Writer {
+        /// Append `value` as a `bits` wide word
+        fn word(&mut self, bits: usize, value: u64) {
+            let bytes = if self.is_le {
+                value.to_le_bytes()[..bits / 8].to_vec()
+            } else {
+                value.to_be_bytes()[8 - bits / 8..].to_vec()
+            };
+            self.data.extend(bytes);
+        }
+
+        fn pad(&mut self, len: usize) {
+            self.data.resize(len, 0);
+        }
+    }
+
+    /// A minimal ELF file with the given `(name, type, link, contents)` sections after the null
+    /// one, followed by the section name table. Links are section indexes, counting the null one
+    fn fixture(
+        is_64: bool,
+        is_le: bool,
+        machine: u16,
+        sections: &[(&str, u32, u32, &[u8])],
+    ) -> Vec<u8> {
+        let (bits, header_size) = if is_64 { (64, 64) } else { (32, 52) };
+        let mut out = Writer {
+            is_le,
+            data: vec![0; header_size],
+        };
+
+        let mut names = vec![0];
+        let mut headers = vec![(0, 0, 0, 0, 0)];
+        for &(name, kind, link, contents) in sections {
+            headers.push((names.len(), kind, out.data.len(), contents.len(), link));
+            names.extend(name.as_bytes());
+            names.push(0);
+            out.data.extend(contents);
+        }
+        headers.push((names.len(), SHT_STRTAB, out.data.len(), names.len() + 10, 0));
+        names.extend(b".shstrtab\0");
+        out.data.extend(&names);
+
+        let shoff = out.data.len();
+        for (name, kind, offset, size, link) in &headers {
+            let start = out.data.len();
+            out.word(32, *name as u64);
+            out.word(32, (*kind).into());
+            out.word(bits, 0);
+            out.word(bits, 0);
+            out.word(bits, *offset as u64);
+            out.word(bits, *size as u64);
+            out.word(32, (*link).into());
+            out.pad(start + if is_64 { 64 } else { 40 });
+        }
+
+        let mut header = Writer {
+            is_le,
+            data: b"\x7fELF".to_vec(),
+        };
+        header
+            .data
+            .extend([if is_64 { 2 } else { 1 }, if is_le { 1 } else { 2 }, 1]);
+        header.pad(16);
+        header.word(16, 3);
+        header.word(16, machine.into());
+        header.word(32, 1);
+        header.pad(if is_64 { 0x28 } else { 0x20 });
+        header.word(bits, shoff as u64);
+        header.pad(if is_64 { 0x3a } else { 0x2e });
+        header.word(16, if is_64 { 64 } else { 40 });
+        header.word(16, headers.len() as u64);
+        header.word(16, headers.len() as u64 - 1);
+        out.data[..header.data.len()].copy_from_slice(&header.data);
+
+        out.data
+    }
+
+    /// A dynamic table with the given entries and the DT_NULL that ends it
+    fn dynamic(is_64: bool, is_le: bool, entries: &[(u64, u64)]) -> Vec<u8> {
+        let mut table = Writer {
+            is_le,
+            data: Vec::new(),
+        };
+        let bits = if is_64 { 64 } else { 32 };
+        for &(tag, value) in entries.iter().chain([&(0, 0)]) {
+            table.word(bits, tag);
+            table.word(bits, value);
+        }
+        table.data
+    }
+
+    /// A shared library needing two others, as a 64-bit little endian x86_64 file or a 32-bit
+    /// big endian powerpc one
+    fn library(is_64: bool, is_le: bool) -> Vec<u8> {
+        let dynstr = b"\0libssl.so.3\0libc.so.6\0libfoo.so.1\0";
+        let dynamic = dynamic(
+            is_64,
+            is_le,
+            &[(DT_NEEDED, 1), (DT_NEEDED, 13), (DT_SONAME, 23)],
+        );
+        let machine = if is_64 { 62 } else { 20 };
+        let sections: &[(&str, u32, u32, &[u8])] = &[
+            (".dynstr", SHT_STRTAB, 0, dynstr),
+            (".dynamic", SHT_DYNAMIC, 1, &dynamic),
+            (".note.gnu.build-id", SHT_NOTE, 0, &[0; 36]),
+        ];
+        fixture(is_64, is_le, machine, sections)
+    }
+
+    #[test]
+    fn shared_library() {
+        for (is_64, is_le, machine) in [(true, true, "x86_64"), (false, false, "powerpc")] {
+            let data = library(is_64, is_le);
+            let elf = Elf::parse(&data).unwrap().unwrap();
+            assert_eq!(elf.is_64(), is_64);
+            assert_eq!(machine_name(elf.machine), machine);
+            assert_eq!(elf.needed().unwrap(), ["libssl.so.3", "libc.so.6"]);
+            assert_eq!(elf.soname().unwrap().as_deref(), Some("libfoo.so.1"));
+            assert!(elf.section(".note.gnu.build-id").is_some());
+            assert!(elf.section(".gnu_debuglink").is_none());
+        }
+    }
+
+    #[test]
+    fn without_dynamic_section() {
+        let data = fixture(true, true, 183, &[]);
+        let elf = Elf::parse(&data).unwrap().unwrap();
+        assert_eq!(machine_name(elf.machine), "aarch64");
+        assert!(elf.needed().unwrap().is_empty());
+        assert_eq!(elf.soname().unwrap(), None);
+        assert!(elf.section(".note.gnu.build-id").is_none());
+    }
+
+    #[test]
+    fn not_elf() {
+        assert!(Elf::parse(b"#!/bin/sh\n").unwrap().is_none());
+        assert!(Elf::parse(b"\x7fEL").unwrap().is_none());
+        assert!(Elf::parse(b"").unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated() {
+        let data = library(true, true);
+        let shoff = u64::from_le_bytes(data[0x28..0x30].try_into().unwrap()) as usize;
+        for len in [6, 0x20, 63, shoff + 10, shoff + 4 * 64 + 30] {
+            assert!(Elf::parse(&data[..len]).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn offsets_out_of_range() {
+        let mut data = library(true, true);
+        // the section table offset near the end of the address space
+        data[0x28..0x30].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        assert!(Elf::parse(&data).is_err());
+
+        // the .dynamic section, the second after the null one, starting past the end of the file
+        let mut data = library(true, true);
+        let shoff = u64::from_le_bytes(data[0x28..0x30].try_into().unwrap()) as usize;
+        let offset = shoff + 2 * 64 + 24;
+        data[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let elf = Elf::parse(&data).unwrap().unwrap();
+        assert!(elf.needed().is_err());
+    }
+
+    #[test]
+    fn test_binary() {
+        let data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let elf = Elf::parse(&data).unwrap().unwrap();
+        assert_eq!(Some(elf.machine), arch_machine(std::env::consts::ARCH));
+        assert!(elf
+            .needed()
+            .unwrap()
+            .iter()
+            .any(|n| n.starts_with("libc.so")));
+    }
+}
@@ -0,0 +1,4738 @@
+//! Build rpm packages from cargo packages, the library behind the `cargo-make-rpm` binary.
+//!
+//! [`run`] does everything the command line does, [`load_manifest`], [`cargo_build`] and
+//! [`build_package`] are its steps for tools that drive the packaging themselves. The options
+//! are the public fields of [`Cli`], starting from [`Cli::default`], and the options of a
+//! package are the [`RPMOptions`] of its [`Package::metadata`]
+
+mod bundle;
+mod cfg;
+mod diff;
+mod elf;
+mod report;
+mod spec;
+mod triplet;
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use bundle::write_bundle;
+use cfg::Cfg;
+use clap::{Parser, ValueEnum};
+use elf::Elf;
+use flate2::write::GzEncoder;
+use pgp::{Deserializable, SignedSecretKey};
+use regex::Regex;
+use report::Report;
+use rpm::{
+    signature::{
+        pgp::{Signer, Verifier},
+        Signing,
+    },
+    Dependency, FileOptions, FileVerifyFlags,
+};
+use serde::{de, de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+pub use triplet::Triplet;
+use triplet::RPM_ARCHES;
+
+/// The output of `cargo metadata`
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub packages: Vec<Package>,
+    pub workspace_members: Option<Vec<String>>,
+    pub workspace_root: Option<String>,
+    /// The directory cargo builds in, which follows `CARGO_TARGET_DIR` and `build.target-dir`
+    pub target_directory: Option<String>,
+}
+
+/// A package of the workspace, as reported by `cargo metadata`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub license: Option<String>,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    pub targets: Vec<Target>,
+    pub manifest_path: String,
+    pub metadata: Option<Metadata>,
+    pub homepage: Option<String>,
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+}
+
+/// The parts of the workspace `Cargo.toml` needed to resolve inherited fields
+#[derive(Deserialize, Debug)]
+struct WorkspaceManifest {
+    workspace: Option<Workspace>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Workspace {
+    package: Option<WorkspacePackage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct WorkspacePackage {
+    license: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+}
+
+/// The `[package.metadata]` of a package, only the `rpm` section is read
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Metadata {
+    pub rpm: Option<RPMOptions>,
+}
+
+/// The options in a package's `[package.metadata.rpm]` section, with the same names as the keys
+/// of the section
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RPMOptions {
+    #[serde(default)]
+    pub compression: Compression,
+    pub signing_key: Option<OneOrMany<String>>,
+    pub dependencies: Option<Vec<String>>,
+    pub conflicts: Option<Vec<String>>,
+    pub assets: Option<Vec<Asset>>,
+    /// TOML or JSON file with an `assets` list added after the inline assets
+    pub assets_file: Option<String>,
+    pub preinstall: Option<String>,
+    pub postinstall: Option<String>,
+    pub preuninstall: Option<String>,
+    pub postuninstall: Option<String>,
+    pub license_format: Option<LicenseFormat>,
+    pub license_map: Option<BTreeMap<String, String>>,
+    pub target_kinds: Option<Vec<TargetKind>>,
+    pub arch: Option<String>,
+    pub ghost_bins: Option<Vec<String>>,
+    /// Example targets to build and install alongside the binaries
+    pub examples: Option<Vec<String>>,
+    /// Directory the examples are installed in
+    pub examples_dir: Option<String>,
+    /// Prefix of the installed names of the examples, defaults to the package name and a `-`
+    pub examples_prefix: Option<String>,
+    #[serde(default)]
+    pub assets_only: bool,
+    /// Package only the dependencies, without any files
+    #[serde(default)]
+    pub meta: bool,
+    pub build_host: Option<String>,
+    /// Target triples to build for when none are given on the command line
+    pub targets: Option<Vec<String>>,
+    /// CPU variant appended to the arch, e.g. `v3` for `x86_64_v3`
+    pub arch_variant: Option<String>,
+    /// File with the description of the package, the crate's description is the summary
+    pub description_file: Option<String>,
+    /// Named sets of options merged over these ones with `--rpm-profile`
+    pub profiles: Option<BTreeMap<String, serde_json::Value>>,
+    /// Scriptlets of single binaries, by binary name
+    pub bins: Option<BTreeMap<String, BinOptions>>,
+    /// Symlinks to create, from the link path to its target
+    pub symlinks: Option<BTreeMap<String, String>>,
+    /// Directories the package owns without installing anything in them
+    pub owned_dirs: Option<Vec<OwnedDir>>,
+    /// Dist tag appended to the release, overridden by `--dist`
+    pub dist: Option<String>,
+    /// URL of the package instead of the crate's homepage
+    pub url: Option<String>,
+    /// Arches the package is never built for
+    pub exclude_arch: Option<Vec<String>>,
+    /// Arches the package is only built for
+    pub exclusive_arch: Option<Vec<String>>,
+    /// Options that only apply to targets matching a triple or `cfg(...)` predicate
+    pub target: Option<BTreeMap<String, TargetOptions>>,
+    /// Subpackages with the assets of optional cargo features, by feature name
+    pub features: Option<BTreeMap<String, FeatureOptions>>,
+}
+
+/// A `<name>-<feature>` subpackage, written when the feature is enabled in the build
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureOptions {
+    /// Summary of the subpackage, defaults to the package's with the feature appended
+    pub summary: Option<String>,
+    pub assets: Vec<Asset>,
+    pub dependencies: Option<Vec<String>>,
+}
+
+/// The options of a single binary, in `bins`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BinOptions {
+    /// Whether the binary is installed in `/usr/bin`
+    pub bindir: Option<bool>,
+    /// File name the binary is installed as instead of its target name
+    pub install_name: Option<String>,
+    /// Path the binary is installed at instead of `/usr/bin`, even without `bindir`
+    pub path: Option<String>,
+    pub preinstall: Option<String>,
+    pub postinstall: Option<String>,
+    pub preuninstall: Option<String>,
+    pub postuninstall: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ScriptletKind {
+    PreInstall,
+    PostInstall,
+    PreUninstall,
+    PostUninstall,
+}
+
+impl ScriptletKind {
+    /// The metadata key of the scriptlet
+    fn key(self) -> &'static str {
+        match self {
+            ScriptletKind::PreInstall => "preinstall",
+            ScriptletKind::PostInstall => "postinstall",
+            ScriptletKind::PreUninstall => "preuninstall",
+            ScriptletKind::PostUninstall => "postuninstall",
+        }
+    }
+}
+
+/// Split the `#!` interpreter line off a script
+fn split_shebang(script: &str) -> (Option<&str>, &str) {
+    match script.strip_prefix("#!") {
+        Some(rest) => {
+            let (interpreter, script) = rest.split_once('\n').unwrap_or((rest, ""));
+            (Some(interpreter.trim()), script)
+        }
+        None => (None, script),
+    }
+}
+
+impl BinOptions {
+    fn scriptlet(&self, kind: ScriptletKind) -> Option<&String> {
+        match kind {
+            ScriptletKind::PreInstall => self.preinstall.as_ref(),
+            ScriptletKind::PostInstall => self.postinstall.as_ref(),
+            ScriptletKind::PreUninstall => self.preuninstall.as_ref(),
+            ScriptletKind::PostUninstall => self.postuninstall.as_ref(),
+        }
+    }
+}
+
+/// The options of a `target` table, for the targets matching its triple or predicate
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TargetOptions {
+    pub dependencies: Option<Vec<String>>,
+    pub preinstall: Option<String>,
+    pub postinstall: Option<String>,
+    pub preuninstall: Option<String>,
+    pub postuninstall: Option<String>,
+}
+
+impl TargetOptions {
+    fn scriptlet(&self, kind: ScriptletKind) -> Option<&String> {
+        match kind {
+            ScriptletKind::PreInstall => self.preinstall.as_ref(),
+            ScriptletKind::PostInstall => self.postinstall.as_ref(),
+            ScriptletKind::PreUninstall => self.preuninstall.as_ref(),
+            ScriptletKind::PostUninstall => self.postuninstall.as_ref(),
+        }
+    }
+}
+
+impl RPMOptions {
+    /// Whether the package is built for `arch`, according to `exclude_arch` and `exclusive_arch`
+    fn builds_for(&self, arch: &str) -> bool {
+        let excluded = self
+            .exclude_arch
+            .as_ref()
+            .is_some_and(|a| a.iter().any(|a| a == arch));
+        let exclusive = self
+            .exclusive_arch
+            .as_ref()
+            .is_none_or(|a| a.iter().any(|a| a == arch));
+
+        !excluded && exclusive
+    }
+
+    /// Make sure `exclude_arch` and `exclusive_arch` only name arches rpm knows
+    fn check_arches(&self) -> Result<(), String> {
+        let arches = self.exclude_arch.iter().chain(&self.exclusive_arch);
+        for arch in arches.flatten() {
+            if !RPM_ARCHES.contains(&arch.as_str()) {
+                return Err(format!("unknown rpm arch '{arch}'"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The package's scriptlet of `kind` followed by the ones of the target tables that apply to
+    /// `triplet` and the ones of the packaged binaries `bins`
+    fn scriptlet(
+        &self,
+        kind: ScriptletKind,
+        triplet: &Triplet,
+        bins: &[&str],
+    ) -> Result<Option<rpm::Scriptlet>, String> {
+        let base = match kind {
+            ScriptletKind::PreInstall => self.preinstall.as_ref(),
+            ScriptletKind::PostInstall => self.postinstall.as_ref(),
+            ScriptletKind::PreUninstall => self.preuninstall.as_ref(),
+            ScriptletKind::PostUninstall => self.postuninstall.as_ref(),
+        };
+
+        let target_options = self.target_options(triplet)?;
+        let scripts: Vec<&str> = std::iter::once(base)
+            .chain(target_options.iter().map(|t| t.scriptlet(kind)))
+            .chain(bins.iter().map(|bin| {
+                self.bins
+                    .as_ref()
+                    .and_then(|b| b.get(*bin))
+                    .and_then(|b| b.scriptlet(kind))
+            }))
+            .flatten()
+            .map(|s| s.trim_end())
+            .collect();
+
+        if scripts.is_empty() {
+            return Ok(None);
+        }
+
+        // rpm runs the scriptlet with its program instead of reading a shebang, so the shebang of
+        // the first part becomes the program and the others can only repeat it
+        let (interpreter, _) = split_shebang(scripts[0]);
+        let mut body = Vec::with_capacity(scripts.len());
+        for script in scripts {
+            let (shebang, script) = split_shebang(script);
+            if shebang != interpreter {
+                return Err(format!(
+                    "the {} scriptlets of the package, its targets and its bins need the same #! interpreter",
+                    kind.key()
+                ));
+            }
+            body.push(script);
+        }
+
+        let scriptlet = rpm::Scriptlet::new(body.join("\n"));
+        Ok(Some(match interpreter {
+            Some(interpreter) => scriptlet.prog(interpreter.split_whitespace().collect()),
+            None => scriptlet,
+        }))
+    }
+
+    /// The target specific options that apply to `triplet`
+    fn target_options(&self, triplet: &Triplet) -> Result<Vec<&TargetOptions>, String> {
+        let mut matching = Vec::new();
+        for (target, options) in self.target.iter().flatten() {
+            if Cfg::parse_target(target)?.matches(triplet) {
+                matching.push(options);
+            }
+        }
+
+        Ok(matching)
+    }
+}
+
+/// An additional file to install
+///
+/// Either the short `[source, dest, mode]` form or a table, which can also
+/// provide the contents of the file inline instead of a source path and
+/// leave out the mode.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum Asset {
+    Short(String, String, String),
+    Table(AssetTable),
+}
+
+/// The table form of an [`Asset`]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AssetTable {
+    pub source: Option<String>,
+    pub content: Option<String>,
+    pub dest: String,
+    pub mode: Option<String>,
+    pub verify: Option<String>,
+    #[serde(default)]
+    pub gzip: bool,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub caps: Option<String>,
+    pub doc: Option<bool>,
+    pub license: Option<bool>,
+}
+
+impl<'de> Deserialize<'de> for Asset {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        if value.is_array() {
+            let (source, dest, mode) = serde_json::from_value(value).map_err(de::Error::custom)?;
+            return Ok(Asset::Short(source, dest, mode));
+        }
+
+        let expected = "an asset as a [source, dest, mode] list or a table";
+        deserialize_table(value, expected).map(Asset::Table)
+    }
+}
+
+/// Deserialize the table form of an option, failing with `expected` when it isn't a table. An
+/// untagged enum can't tell an unknown key apart from a value that matches none of its forms,
+/// so the tables are checked on their own
+fn deserialize_table<T: DeserializeOwned, E: de::Error>(
+    value: serde_json::Value,
+    expected: &str,
+) -> Result<T, E> {
+    if !value.is_object() {
+        return Err(E::custom(format!("expected {expected}")));
+    }
+
+    serde_json::from_value(value).map_err(|e| E::custom(explain_unknown_field(&e)))
+}
+
+/// The contents of an `assets_file`
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct AssetsFile {
+    assets: Vec<Asset>,
+}
+
+/// A directory the package owns, either just its path or a table that can also give its mode
+/// and owner
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum OwnedDir {
+    Path(String),
+    Table(OwnedDirTable),
+}
+
+/// The table form of an [`OwnedDir`]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OwnedDirTable {
+    pub path: String,
+    pub mode: Option<String>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for OwnedDir {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        if let serde_json::Value::String(path) = value {
+            return Ok(OwnedDir::Path(path));
+        }
+
+        deserialize_table(value, "an owned dir as a path or a table").map(OwnedDir::Table)
+    }
+}
+
+impl OwnedDir {
+    fn path(&self) -> &str {
+        match self {
+            OwnedDir::Path(path) | OwnedDir::Table(OwnedDirTable { path, .. }) => path,
+        }
+    }
+
+    /// The directory's permissions, `755` unless configured
+    fn mode(&self) -> Result<u16, String> {
+        match self {
+            OwnedDir::Table(OwnedDirTable {
+                mode: Some(mode), ..
+            }) => parse_mode(mode, self.path()),
+            _ => Ok(0o755),
+        }
+    }
+
+    /// The file options of the directory entry
+    fn file_options(&self) -> Result<rpm::FileOptionsBuilder, String> {
+        let path = self.path();
+        if !path.starts_with('/') {
+            return Err(format!("owned dir {path} needs an absolute path"));
+        }
+
+        let mut options =
+            FileOptions::new(path.trim_end_matches('/')).mode(0o040000 | self.mode()?);
+        if let OwnedDir::Table(OwnedDirTable { user, group, .. }) = self {
+            if let Some(user) = user {
+                options = options.user(parse_owner(user, "user", path)?);
+            }
+
+            if let Some(group) = group {
+                options = options.group(parse_owner(group, "group", path)?);
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+impl Asset {
+    /// Whether the file installed at `dest` is marked as documentation and as a license.
+    /// Files in the doc and license directories are marked like rpmbuild's `%doc` and
+    /// `%license` unless the asset says otherwise
+    fn marks(&self, dest: &str) -> Result<(bool, bool), String> {
+        let (doc, license) = match self {
+            Asset::Table(AssetTable { doc, license, .. }) => (*doc, *license),
+            Asset::Short(..) => (None, None),
+        };
+        let doc = doc.unwrap_or(dest.starts_with("/usr/share/doc/"));
+        let license = license.unwrap_or(dest.starts_with("/usr/share/licenses/"));
+        if doc && license {
+            return Err(format!("asset {dest} can't be both doc and license"));
+        }
+
+        Ok((doc, license))
+    }
+}
+
+/// A single value or a list of values
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    fn as_slice(&self) -> &[T] {
+        match self {
+            OneOrMany::One(value) => std::slice::from_ref(value),
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// A cargo target of a package, like a bin or lib
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Target {
+    pub name: String,
+    /// The kinds of the target, like `bin` or `cdylib`
+    pub kind: Vec<String>,
+}
+
+/// The kinds of cargo targets that can be packaged
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    Bin,
+    Cdylib,
+    Dylib,
+    Staticlib,
+}
+
+impl TargetKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TargetKind::Bin => "bin",
+            TargetKind::Cdylib => "cdylib",
+            TargetKind::Dylib => "dylib",
+            TargetKind::Staticlib => "staticlib",
+        }
+    }
+}
+
+impl Package {
+    /// The options of the package's `[package.metadata.rpm]` section, if it has one
+    pub fn rpm_options(&self) -> Option<&RPMOptions> {
+        self.metadata.as_ref().and_then(|m| m.rpm.as_ref())
+    }
+
+    /// The kinds of targets that get installed, only binaries unless configured otherwise
+    fn target_kinds(&self) -> &[TargetKind] {
+        self.rpm_options()
+            .and_then(|r| r.target_kinds.as_deref())
+            .unwrap_or(&[TargetKind::Bin])
+    }
+
+    /// Whether only the assets are packaged, without building any targets
+    fn assets_only(&self) -> bool {
+        self.rpm_options().is_some_and(|r| r.assets_only || r.meta)
+    }
+
+    /// The URL of the package, the configured one or else the crate's homepage
+    fn url(&self) -> Option<&str> {
+        self.rpm_options()
+            .and_then(|r| r.url.as_deref())
+            .or(self.homepage.as_deref())
+    }
+
+    /// Whether the package only carries dependencies, without any files
+    fn is_meta(&self) -> bool {
+        self.rpm_options().is_some_and(|r| r.meta)
+    }
+
+    fn has_packaged_targets(&self) -> bool {
+        self.assets_only() || !self.packaged_targets().is_empty() || !self.examples().is_empty()
+    }
+
+    /// The names of the example targets that get installed
+    fn examples(&self) -> &[String] {
+        match self.rpm_options() {
+            Some(r) if !self.assets_only() => r.examples.as_deref().unwrap_or_default(),
+            _ => &[],
+        }
+    }
+
+    /// Why a package without packaged targets is skipped
+    fn skip_reason(&self) -> String {
+        let wanted: Vec<&str> = self.target_kinds().iter().map(TargetKind::as_str).collect();
+        let found: BTreeSet<&str> = self
+            .targets
+            .iter()
+            .flat_map(|t| &t.kind)
+            .map(String::as_str)
+            .collect();
+        let found: Vec<&str> = found.into_iter().collect();
+
+        if found.is_empty() {
+            return "has no targets".to_owned();
+        }
+
+        format!(
+            "has no {} target, only {}",
+            wanted.join(" or "),
+            found.join(", ")
+        )
+    }
+
+    /// The targets that get installed and the kind they're installed as
+    fn packaged_targets(&self) -> Vec<(&Target, TargetKind)> {
+        if self.assets_only() {
+            return Vec::new();
+        }
+
+        let mut targets = Vec::new();
+        for target in &self.targets {
+            for &kind in self.target_kinds() {
+                if target.kind.iter().any(|k| k == kind.as_str()) {
+                    targets.push((target, kind));
+                }
+            }
+        }
+
+        targets
+    }
+
+    /// The `cargo build` arguments selecting only the targets that get installed
+    fn build_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (target, kind) in self.packaged_targets() {
+            match kind {
+                TargetKind::Bin => args.extend(["--bin".to_owned(), target.name.clone()]),
+                _ if !args.iter().any(|a| a == "--lib") => args.push("--lib".to_owned()),
+                _ => {}
+            }
+        }
+
+        for example in self.examples() {
+            args.extend(["--example".to_owned(), example.clone()]);
+        }
+
+        args
+    }
+
+    /// The file name the bin target `bin` is installed as
+    fn install_name<'a>(&'a self, bin: &'a str) -> &'a str {
+        self.bin_options(bin)
+            .and_then(|b| b.install_name.as_deref())
+            .unwrap_or(bin)
+    }
+
+    fn bin_options(&self, bin: &str) -> Option<&BinOptions> {
+        self.rpm_options()
+            .and_then(|r| r.bins.as_ref())
+            .and_then(|b| b.get(bin))
+    }
+
+    /// Where the binary is installed, its `path` or else `/usr/bin` unless it's left out of it
+    /// by `bindir = false` or `--no-default-bindir`, then it isn't installed at all
+    fn bin_dest(&self, bin: &str, default_bindir: bool) -> Option<String> {
+        let options = self.bin_options(bin);
+        if let Some(path) = options.and_then(|b| b.path.as_ref()) {
+            return Some(path.clone());
+        }
+
+        options
+            .and_then(|b| b.bindir)
+            .unwrap_or(default_bindir)
+            .then(|| format!("/usr/bin/{}", self.install_name(bin)))
+    }
+
+    /// Make sure the install names are file names and the paths absolute, and that no two
+    /// binaries are installed at the same path
+    fn check_install_names(&self) -> Result<(), String> {
+        let mut installed = BTreeMap::new();
+        for (target, kind) in self.packaged_targets() {
+            if !matches!(kind, TargetKind::Bin) {
+                continue;
+            }
+
+            let name = self.install_name(&target.name);
+            if name.is_empty() || name.contains('/') {
+                return Err(format!(
+                    "invalid install_name '{name}' of bin {}",
+                    target.name
+                ));
+            }
+
+            let options = self.bin_options(&target.name);
+            if let Some(path) = options.and_then(|b| b.path.as_ref()) {
+                if options.is_some_and(|b| b.install_name.is_some()) {
+                    return Err(format!(
+                        "bin {} has both install_name and path, the path names the file",
+                        target.name
+                    ));
+                }
+
+                if !path.starts_with('/') || path.ends_with('/') {
+                    return Err(format!(
+                        "invalid path '{path}' of bin {}, expected the absolute path of a file",
+                        target.name
+                    ));
+                }
+            }
+
+            let Some(dest) = self.bin_dest(&target.name, true) else {
+                continue;
+            };
+            if let Some(other) = installed.insert(dest.clone(), &target.name) {
+                return Err(format!(
+                    "bins {other} and {} are both installed as {dest}",
+                    target.name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A message emitted by `cargo build --message-format=json`
+#[derive(Deserialize, Debug)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum BuildMessage {
+    BuildScriptExecuted {
+        package_id: String,
+        out_dir: PathBuf,
+    },
+    CompilerArtifact {
+        package_id: String,
+        target: Target,
+        filenames: Vec<PathBuf>,
+        executable: Option<PathBuf>,
+        features: Vec<String>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// What the cargo build reported about the packages it built
+#[derive(Debug, Default)]
+pub struct BuildOutput {
+    /// The `OUT_DIR` of each package with a build script, by package id
+    out_dirs: HashMap<String, PathBuf>,
+    /// The executables produced, by package id and target name
+    executables: HashMap<(String, String), PathBuf>,
+    /// The example executables produced, kept apart since examples can share names with bins
+    examples: HashMap<(String, String), PathBuf>,
+    /// All the files produced, by package id and target name
+    filenames: HashMap<(String, String), Vec<PathBuf>>,
+    /// The features enabled in the build, by package id
+    features: HashMap<String, BTreeSet<String>>,
+}
+
+impl BuildOutput {
+    /// The `OUT_DIR` of the build script of a package
+    pub fn out_dir(&self, package_id: &str) -> Option<&Path> {
+        self.out_dirs.get(package_id).map(PathBuf::as_path)
+    }
+
+    /// The executable built for the bin target `target` of a package
+    pub fn executable(&self, package_id: &str, target: &str) -> Option<&Path> {
+        self.executables
+            .get(&(package_id.to_owned(), target.to_owned()))
+            .map(PathBuf::as_path)
+    }
+
+    /// The files built for the target `target` of a package, like a library's `.so` and `.a`
+    pub fn files(&self, package_id: &str, target: &str) -> &[PathBuf] {
+        self.filenames
+            .get(&(package_id.to_owned(), target.to_owned()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The features that were enabled for a package
+    pub fn features(&self, package_id: &str) -> Option<&BTreeSet<String>> {
+        self.features.get(package_id)
+    }
+}
+
+/// The rpms [`build_package`] wrote for a package
+#[derive(Debug, Clone)]
+pub struct BuiltPackage {
+    pub name: String,
+    /// The version with `--set-version` and `--version-suffix` applied
+    pub version: String,
+    /// The release, `None` when it's the default of 1
+    pub release: Option<String>,
+    pub arch: String,
+    /// The rpms of the package and its subpackages, a copy for each key when there are several
+    /// signing keys
+    pub paths: Vec<PathBuf>,
+    /// Whether the rpms were up to date and weren't rebuilt
+    pub up_to_date: bool,
+    /// How far `--test-install` got, `None` when the rpms weren't tested
+    pub tested: Option<InstallTest>,
+}
+
+/// What `--test-install` checked of a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallTest {
+    /// `rpm -i --test` accepts the rpms, without podman
+    Installs,
+    /// The rpms installed in a container and their binaries ran
+    Runs,
+}
+
+/// The command line options, which also control the packaging when used as a library
+#[derive(Parser, Debug)]
+#[clap(version)]
+pub struct Cli {
+    #[clap(last = true, allow_hyphen_values = true, hide = true)]
+    pub cargo_args: Vec<String>,
+    /// Compression algorithm to use, instead of the one in the metadata
+    #[clap(long, env = "CARGO_MAKE_RPM_COMPRESSION")]
+    pub compression: Option<Compression>,
+    /// Workspace member to build, by name or path
+    #[clap(long, short)]
+    pub package: Option<String>,
+    /// Target triple to build for, can be given multiple times
+    #[clap(long)]
+    pub target: Vec<String>,
+    /// Signing key to use, can be given multiple times
+    #[clap(long, short = 'k')]
+    pub signing_key: Vec<String>,
+    /// Passphrase of protected signing keys
+    #[clap(
+        long,
+        env = "CARGO_MAKE_RPM_SIGNING_KEY_PASSPHRASE",
+        hide_env_values = true
+    )]
+    pub signing_key_passphrase: Option<String>,
+    /// Profile from `[package.metadata.rpm.profiles]` to merge over the base options
+    #[clap(long, value_name = "PROFILE")]
+    pub rpm_profile: Option<String>,
+    /// Install the license and readme files of packages that don't configure any assets
+    #[clap(long)]
+    pub defaults: bool,
+    /// Format of the License header
+    #[clap(long)]
+    pub license_format: Option<LicenseFormat>,
+    /// Use LicenseRef-Proprietary for packages without a license instead of failing
+    #[clap(long)]
+    pub allow_missing_license: bool,
+    /// Version to package instead of the one in Cargo.toml
+    #[clap(long, value_parser = parse_rpm_version)]
+    pub set_version: Option<String>,
+    /// Release of the package instead of 1
+    #[clap(long, value_name = "RELEASE", value_parser = parse_rpm_version)]
+    pub set_release: Option<String>,
+    /// Dist tag appended to the release, e.g. `.fc40`, or `auto` to detect it from the host
+    #[clap(long, value_name = "DIST")]
+    pub dist: Option<String>,
+    /// Build identifier appended to the version, separated with `~` unless it starts with `~` or `^`
+    #[clap(long, value_parser = parse_rpm_version)]
+    pub version_suffix: Option<String>,
+    /// Append the current git commit to the VCS tag
+    #[clap(long)]
+    pub vcs_commit: bool,
+    /// Vendor of all the packages instead of their authors
+    #[clap(long, env = "CARGO_MAKE_RPM_VENDOR")]
+    pub vendor: Option<String>,
+    /// Build host recorded in the rpm, e.g. the build system that produced it
+    #[clap(long)]
+    pub build_host: Option<String>,
+    /// Unix time recorded as the build time and clamping file mtimes, for reproducible rpms
+    #[clap(long, env = "SOURCE_DATE_EPOCH", value_name = "EPOCH")]
+    pub source_date: Option<u32>,
+    /// Print which packages are skipped and why
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Print the time spent in each phase of packaging
+    #[clap(long)]
+    pub timings: bool,
+    /// Continue packaging the remaining packages when one fails
+    #[clap(long)]
+    pub keep_going: bool,
+    /// Build meta packages that only carry the dependencies, without any files
+    #[clap(long)]
+    pub meta: bool,
+    /// Build the binaries without installing them in /usr/bin, except the ones with a bins path
+    #[clap(long)]
+    pub no_default_bindir: bool,
+    /// Split the assets into `<name>-part<n>` packages of at most SIZE bytes, e.g. 500M
+    #[clap(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_size: Option<u64>,
+    /// Staging directory mirroring the installed filesystem, every file in it is packaged
+    #[clap(long, value_name = "DIR", conflicts_with = "standalone")]
+    pub buildroot: Option<PathBuf>,
+    /// Print an equivalent `.spec` file for each package instead of building
+    #[clap(long, conflicts_with = "standalone")]
+    pub print_spec: bool,
+    /// Write packages that don't contain any files instead of failing
+    #[clap(long)]
+    pub allow_empty: bool,
+    /// File listing the install paths the package and its subpackages must contain, one per line
+    #[clap(long)]
+    pub expect_files: Option<PathBuf>,
+    /// Write a JSON report of the rpms that were written, for CI tooling
+    #[clap(long, value_name = "PATH")]
+    pub report_json: Option<PathBuf>,
+    /// Write a .tar.gz with the rpms that were written, their checksums and the JSON report
+    #[clap(long, value_name = "PATH")]
+    pub bundle: Option<PathBuf>,
+    /// Print the detected target and its rpm arch, then exit
+    #[clap(long)]
+    pub show_target_info: bool,
+    /// Fail instead of overwriting rpms that already exist
+    #[clap(long)]
+    pub no_clobber: bool,
+    /// Skip packages whose rpm already exists, without building them
+    #[clap(long, conflicts_with = "force")]
+    pub skip_existing: bool,
+    /// Overwrite rpms that already exist and rebuild up to date ones, overrides --no-clobber
+    #[clap(long)]
+    pub force: bool,
+    /// Package a prebuilt binary instead of a cargo project, without running cargo
+    #[clap(long, value_name = "BINARY", requires_all = ["name", "set_version", "description"])]
+    pub standalone: Option<PathBuf>,
+    /// Name of the standalone package
+    #[clap(long, requires = "standalone")]
+    pub name: Option<String>,
+    /// License of the standalone package
+    #[clap(long, requires = "standalone")]
+    pub license: Option<String>,
+    /// Summary of the standalone package
+    #[clap(long, requires = "standalone")]
+    pub description: Option<String>,
+    /// Rpm arch of the standalone package instead of the one of the target, e.g. `noarch` for a script
+    #[clap(long, requires = "standalone", value_parser = parse_arch)]
+    pub arch: Option<String>,
+    /// Public key the signed rpms are checked against after writing them, can be given multiple times
+    #[clap(long, value_name = "PUBLIC_KEY")]
+    pub verify_signature: Vec<PathBuf>,
+    /// Sign an already built rpm in place instead of building
+    #[clap(long, requires = "signing_key", value_name = "RPM")]
+    pub sign_after_build: Option<PathBuf>,
+    /// Compare the headers, dependencies, scriptlets and files of two rpms instead of building
+    #[clap(long, num_args = 2, value_names = ["OLD", "NEW"])]
+    pub diff: Vec<PathBuf>,
+    /// CPU variant appended to the arch, e.g. `v3` for `x86_64_v3`
+    #[clap(long, value_parser = parse_arch_variant)]
+    pub arch_variant: Option<String>,
+    /// Directory to stage generated files in, defaults to `TMPDIR` or the system's temp directory
+    #[clap(long)]
+    pub temp_dir: Option<PathBuf>,
+    /// Directory to write all the rpms to instead of `target/rpm`
+    #[clap(long)]
+    pub out_dir: Option<PathBuf>,
+    /// Run `cargo build` again up to N times when it fails, e.g. when fetching dependencies
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+    /// Permissions of the directories the rpms are written to, e.g. 2775 for a group writable one
+    #[clap(long, value_name = "MODE", value_parser = parse_output_mode)]
+    pub out_dir_mode: Option<u32>,
+    /// Permissions of the written rpms, e.g. 664
+    #[clap(long, value_name = "MODE", value_parser = parse_output_mode)]
+    pub rpm_mode: Option<u32>,
+    /// Require the shared libraries the packaged binaries link to
+    #[clap(long)]
+    pub auto_requires: bool,
+    /// Provide the sonames of the packaged shared libraries
+    #[clap(long)]
+    pub auto_provides: bool,
+    /// Check that packaged binaries have a GNU build-id, needed to match them with debuginfo
+    #[clap(long, value_name = "LEVEL")]
+    pub check_build_id: Option<CheckLevel>,
+    /// Check that the rpms install and their binaries run, in a podman container when available
+    #[clap(long)]
+    pub test_install: bool,
+    /// Container image to test installing in
+    #[clap(
+        long,
+        value_name = "IMAGE",
+        default_value = "fedora",
+        requires = "test_install"
+    )]
+    pub test_image: String,
+    /// Format of notices, warnings and errors, defaults to github when GITHUB_ACTIONS is true
+    #[clap(long, value_name = "FORMAT")]
+    pub annotations: Option<Annotations>,
+}
+
+/// The options of a command line without any arguments, environment variables like
+/// `CARGO_MAKE_RPM_COMPRESSION` still apply. Library users set the fields they need on top
+impl Default for Cli {
+    fn default() -> Self {
+        Cli::parse_from(["cargo-make-rpm"])
+    }
+}
+
+impl Cli {
+    fn annotations(&self) -> Annotations {
+        match self.annotations {
+            Some(annotations) => annotations,
+            None if std::env::var("GITHUB_ACTIONS").is_ok_and(|v| v == "true") => {
+                Annotations::Github
+            }
+            None => Annotations::Plain,
+        }
+    }
+}
+
+/// The format of warnings and errors
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Annotations {
+    /// `notice:`, `warning:` and `error:` lines
+    Plain,
+    /// GitHub Actions workflow commands, which are shown on the run and its pull request
+    Github,
+}
+
+/// Print a notice, warning or error in the format of `--annotations`
+fn annotate(args: &Cli, level: &str, message: &str) {
+    match args.annotations() {
+        Annotations::Plain => eprintln!("{level}: {message}"),
+        Annotations::Github => {
+            // a workflow command ends at the end of the line, so newlines are escaped
+            let message = message
+                .replace('%', "%25")
+                .replace('\r', "%0D")
+                .replace('\n', "%0A");
+            eprintln!("::{level}::{message}");
+        }
+    }
+}
+
+/// How strictly a check is applied
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum CheckLevel {
+    /// Print a warning and continue
+    Warn,
+    /// Fail the package
+    Deny,
+}
+
+/// The compression of the rpm payload
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    #[default]
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+/// The format of the License header
+#[derive(ValueEnum, Default, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LicenseFormat {
+    /// Pass the SPDX expression through unchanged
+    #[default]
+    Spdx,
+    /// Translate the SPDX expression to the legacy Fedora license names
+    Legacy,
+}
+
+/// SPDX identifiers and their legacy Fedora short names
+const LEGACY_LICENSES: &[(&str, &str)] = &[
+    ("0BSD", "0BSD"),
+    ("AGPL-3.0-only", "AGPLv3"),
+    ("AGPL-3.0-or-later", "AGPLv3+"),
+    ("Apache-2.0", "ASL 2.0"),
+    ("BSD-2-Clause", "BSD"),
+    ("BSD-3-Clause", "BSD"),
+    ("BSL-1.0", "Boost"),
+    ("CC0-1.0", "CC0"),
+    ("GPL-2.0-only", "GPLv2"),
+    ("GPL-2.0-or-later", "GPLv2+"),
+    ("GPL-3.0-only", "GPLv3"),
+    ("GPL-3.0-or-later", "GPLv3+"),
+    ("ISC", "ISC"),
+    ("LGPL-2.1-only", "LGPLv2"),
+    ("LGPL-2.1-or-later", "LGPLv2+"),
+    ("LGPL-3.0-only", "LGPLv3"),
+    ("LGPL-3.0-or-later", "LGPLv3+"),
+    ("MIT", "MIT"),
+    ("MPL-2.0", "MPLv2.0"),
+    ("Unicode-DFS-2016", "Unicode"),
+    ("Unlicense", "Unlicense"),
+    ("Zlib", "zlib"),
+];
+
+/// Translate an SPDX license expression to the legacy Fedora format
+///
+/// Identifiers found in `overrides` take precedence over the builtin table,
+/// unknown identifiers are passed through unchanged.
+fn legacy_license(spdx: &str, overrides: Option<&BTreeMap<String, String>>) -> String {
+    // cargo still accepts the deprecated `MIT/Apache-2.0` syntax
+    let spdx = spdx.replace('/', " OR ");
+    let spdx = spdx.replace('(', " ( ").replace(')', " ) ");
+
+    let mut license = String::new();
+    for token in spdx.split_whitespace() {
+        let translated = match token {
+            "OR" => "or",
+            "AND" => "and",
+            "WITH" => "with",
+            id => overrides
+                .and_then(|o| o.get(id))
+                .map(String::as_str)
+                .or_else(|| {
+                    LEGACY_LICENSES
+                        .iter()
+                        .find(|(spdx, _)| spdx == &id)
+                        .map(|(_, legacy)| *legacy)
+                })
+                .unwrap_or(id),
+        };
+
+        if !(license.is_empty() || license.ends_with('(') || translated == ")") {
+            license.push(' ');
+        }
+        license.push_str(translated);
+    }
+
+    license
+}
+
+impl From<Compression> for rpm::CompressionType {
+    /// `None` writes a plain cpio payload without a PayloadCompressor tag,
+    /// rpm reads such payloads transparently through its gzip reader.
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => rpm::CompressionType::None,
+            Compression::Gzip => rpm::CompressionType::Gzip,
+            Compression::Zstd => rpm::CompressionType::Zstd,
+            Compression::Xz => rpm::CompressionType::Xz,
+            Compression::Bzip2 => rpm::CompressionType::Bzip2,
+        }
+    }
+}
+
+/// Parse an octal permission string like `644`, `0644` or `0o644`
+fn parse_mode(mode: &str, asset: &str) -> Result<u16, String> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    u16::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or(format!(
+            "invalid mode '{mode}' for asset '{asset}'; expected octal like 644"
+        ))
+}
+
+/// Parse the verify flags of an asset, like `%verify` in a spec: the aspects `rpm -V` checks,
+/// or with `not` the ones it skips
+fn parse_verify(verify: &str, asset: &str) -> Result<FileVerifyFlags, String> {
+    let mut words = verify.split_whitespace().peekable();
+    let negate = words.next_if_eq(&"not").is_some();
+
+    let mut flags = FileVerifyFlags::NONE;
+    for word in words {
+        flags |= match word {
+            "md5" | "filedigest" => FileVerifyFlags::FILEDIGEST,
+            "size" => FileVerifyFlags::FILESIZE,
+            "link" => FileVerifyFlags::LINKTO,
+            "user" => FileVerifyFlags::USER,
+            "group" => FileVerifyFlags::GROUP,
+            "mtime" => FileVerifyFlags::MTIME,
+            "mode" => FileVerifyFlags::MODE,
+            "rdev" => FileVerifyFlags::RDEV,
+            "caps" => FileVerifyFlags::CAPS,
+            _ => {
+                return Err(format!(
+                    "invalid verify flag '{word}' for asset '{asset}'; expected md5, size, link, user, group, mtime, mode, rdev or caps"
+                ))
+            }
+        };
+    }
+
+    Ok(if negate {
+        FileVerifyFlags::all() - flags
+    } else {
+        flags
+    })
+}
+
+/// Check a user or group name the same way `useradd` does
+fn parse_owner<'a>(name: &'a str, kind: &str, asset: &str) -> Result<&'a str, String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+    if !valid {
+        return Err(format!("invalid {kind} '{name}' for asset '{asset}'"));
+    }
+
+    Ok(name)
+}
+
+fn parse_arch(arch: &str) -> Result<String, String> {
+    if !RPM_ARCHES.contains(&arch) {
+        return Err(format!("unknown rpm arch '{arch}'"));
+    }
+
+    Ok(arch.to_owned())
+}
+
+fn parse_arch_variant(variant: &str) -> Result<String, String> {
+    if variant.is_empty() || !variant.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!(
+            "invalid arch variant '{variant}'; expected letters and digits like v3"
+        ));
+    }
+
+    Ok(variant.to_owned())
+}
+
+fn pad_permission(mode: u16, filepath: &PathBuf) -> Result<u16, Box<dyn Error>> {
+    let ftype = fs::metadata(filepath)?.file_type();
+    if ftype.is_file() {
+        Ok(0o100000 | mode)
+    } else if ftype.is_dir() {
+        Ok(0o040000 | mode)
+    } else if ftype.is_symlink() {
+        Ok(0o120000 | mode)
+    } else {
+        Err("invalid file type".into())
+    }
+}
+
+/// The files and empty directories in a `--buildroot` with the path they're installed at, sorted
+/// so the package doesn't depend on the order directories are read in
+fn buildroot_files(root: &Path) -> Result<Vec<(PathBuf, String)>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| format!("failed to read buildroot {}: {e}", dir.display()))?;
+
+        let mut empty = true;
+        for entry in entries {
+            let path = entry?.path();
+            empty = false;
+            if path.symlink_metadata()?.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+
+        // directories with files are left to owned_dirs, like for assets
+        if empty && dir != root {
+            files.push(dir);
+        }
+    }
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|path| {
+            let relative = path.strip_prefix(root)?;
+            let dest = format!("/{}", relative.to_str().ok_or("invalid buildroot path")?);
+            Ok((path, dest))
+        })
+        .collect()
+}
+
+/// The directories the package owns, those named after the package (like `/usr/share/<name>` or
+/// `/etc/<name>-daemon`) and the ones below them that files are installed in. Shared directories
+/// like `/usr/share` belong to other packages and are left out
+fn owned_dirs(name: &str, dests: &[String]) -> BTreeSet<String> {
+    let named_after = |dir: &&Path| {
+        dir.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+            n.strip_prefix(name)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['-', '_', '.']))
+        })
+    };
+
+    let mut dirs = BTreeSet::new();
+    for dest in dests {
+        let ancestors: Vec<&Path> = Path::new(dest).ancestors().skip(1).collect();
+        let Some(root) = ancestors.iter().rposition(named_after) else {
+            continue;
+        };
+
+        for dir in &ancestors[..=root] {
+            dirs.insert(dir.to_string_lossy().into_owned());
+        }
+    }
+
+    for dest in dests {
+        dirs.remove(dest.trim_end_matches('/'));
+    }
+
+    dirs
+}
+
+/// Check that a version (or part of one) only contains characters rpm allows in a version
+fn parse_rpm_version(version: &str) -> Result<String, String> {
+    if version.is_empty() {
+        return Err("rpm versions can't be empty".to_owned());
+    }
+
+    if version.contains('-') {
+        return Err("rpm versions can't contain -".to_owned());
+    }
+
+    if let Some(c) = version
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || "._+~^".contains(*c)))
+    {
+        return Err(format!("rpm versions can't contain {c}"));
+    }
+
+    Ok(version.to_owned())
+}
+
+/// Fill in license and authors from `[workspace.package]` for members that don't set them.
+///
+/// `cargo metadata` already resolves `license.workspace = true`, this covers members
+/// that omit the fields entirely and expect the workspace values to apply.
+fn inherit_workspace_fields(manifest: &mut Manifest) -> Result<(), Box<dyn Error>> {
+    let needs_inheritance = manifest
+        .packages
+        .iter()
+        .any(|p| p.license.is_none() || p.authors.is_empty());
+
+    let Some(workspace_root) = manifest
+        .workspace_root
+        .as_ref()
+        .filter(|_| needs_inheritance)
+    else {
+        return Ok(());
+    };
+
+    let workspace_manifest = PathBuf::from(workspace_root).join("Cargo.toml");
+    let Ok(contents) = fs::read_to_string(&workspace_manifest) else {
+        return Ok(());
+    };
+
+    let workspace_manifest: WorkspaceManifest = toml::from_str(&contents)?;
+    let Some(defaults) = workspace_manifest.workspace.and_then(|w| w.package) else {
+        return Ok(());
+    };
+
+    for package in &mut manifest.packages {
+        if package.license.is_none() {
+            package.license.clone_from(&defaults.license);
+        }
+
+        if package.authors.is_empty() {
+            package.authors.clone_from(&defaults.authors);
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the workspace member `--package` refers to, either by name or by the path of its directory
+/// or manifest, returns its package id
+fn select_package(manifest: &Manifest, spec: &str) -> Result<String, String> {
+    let members: Vec<&Package> = manifest
+        .packages
+        .iter()
+        .filter(|p| {
+            manifest
+                .workspace_members
+                .as_ref()
+                .is_none_or(|m| m.contains(&p.id))
+        })
+        .collect();
+
+    if let Some(package) = members.iter().find(|p| p.name == spec) {
+        return Ok(package.id.clone());
+    }
+
+    if let Ok(path) = fs::canonicalize(spec) {
+        let package = members.iter().find(|p| {
+            let manifest_path = Path::new(&p.manifest_path);
+            [Some(manifest_path), manifest_path.parent()]
+                .into_iter()
+                .flatten()
+                .any(|p| fs::canonicalize(p).is_ok_and(|p| p == path))
+        });
+
+        if let Some(package) = package {
+            return Ok(package.id.clone());
+        }
+    }
+
+    let names: Vec<&str> = members.iter().map(|p| p.name.as_str()).collect();
+    let similar = similar_names(spec, &names);
+    if similar.is_empty() {
+        return Err(format!(
+            "no such package '{spec}'; the workspace members are {}",
+            names.join(", ")
+        ));
+    }
+
+    Err(format!(
+        "no such package '{spec}'; did you mean {}?",
+        similar.join(", ")
+    ))
+}
+
+/// The names close enough to `name` to be what was meant, the closest first
+fn similar_names<'a>(name: &str, names: &[&'a str]) -> Vec<&'a str> {
+    let mut candidates: Vec<(usize, &str)> =
+        names.iter().map(|n| (edit_distance(name, n), *n)).collect();
+    candidates.sort();
+
+    // allow about one typo for every three characters
+    let max_distance = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .filter(|(distance, _)| *distance <= max_distance)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Rewrite serde's unknown field errors to suggest the closest known key, other errors are kept
+fn explain_unknown_field(err: &serde_json::Error) -> String {
+    let message = err.to_string();
+    let regex =
+        Regex::new(r"^unknown field `([^`]*)`, expected (.*?)(?: at line \d+ column \d+)?$")
+            .unwrap();
+    let Some(captures) = regex.captures(&message) else {
+        return message;
+    };
+
+    let key = &captures[1];
+    let keys: Vec<&str> = Regex::new(r"`([^`]*)`")
+        .unwrap()
+        .captures_iter(captures.get(2).unwrap().as_str())
+        .map(|c| c.get(1).unwrap().as_str())
+        .collect();
+
+    match similar_names(key, &keys).first() {
+        Some(similar) => format!("unknown key `{key}`, did you mean `{similar}`?"),
+        None => format!(
+            "unknown key `{key}`, the known keys are {}",
+            keys.join(", ")
+        ),
+    }
+}
+
+/// Check the `[package.metadata.rpm]` options of each package on their own, so mistakes are
+/// reported with the package and key they're in instead of a position in cargo's output
+fn check_rpm_options(metadata: &serde_json::Value) -> Result<(), String> {
+    let packages = metadata["packages"].as_array().into_iter().flatten();
+    for package in packages {
+        let options = &package["metadata"]["rpm"];
+        if options.is_null() {
+            continue;
+        }
+
+        if let Err(err) = RPMOptions::deserialize(options) {
+            let name = package["name"].as_str().unwrap_or_default();
+            return Err(format!(
+                "invalid [package.metadata.rpm] in {name}: {}",
+                explain_unknown_field(&err)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The edit distance between two strings, counting a swap of adjacent characters like `mdoe`
+/// for `mode` as a single edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 0..a.len() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for j in 0..b.len() {
+            let substitution = previous[j] + usize::from(a[i] != b[j]);
+            row[j + 1] = substitution.min(row[j] + 1).min(previous[j + 1] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                row[j + 1] = row[j + 1].min(before[j - 1] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+
+    previous[b.len()]
+}
+
+/// Add the assets of each package's `assets_file`, relative to the crate directory like the
+/// sources of the assets, to its inline ones
+fn load_assets_files(manifest: &mut Manifest) -> Result<(), Box<dyn Error>> {
+    for package in &mut manifest.packages {
+        let crate_dir = crate_dir(manifest.workspace_root.as_ref(), package);
+        let Some(options) = package.metadata.as_mut().and_then(|m| m.rpm.as_mut()) else {
+            continue;
+        };
+        let Some(ref assets_file) = options.assets_file else {
+            continue;
+        };
+
+        let path = crate_dir.join(assets_file);
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read assets_file {}: {e}", path.display()))?;
+        let file: Result<AssetsFile, String> = if path.extension().is_some_and(|e| e == "json") {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        };
+        let file = file.map_err(|e| format!("invalid assets_file {}: {e}", path.display()))?;
+
+        options
+            .assets
+            .get_or_insert_with(Vec::new)
+            .extend(file.assets);
+    }
+
+    Ok(())
+}
+
+/// Merge the options of the `profile` profile over the base options of each package, options the
+/// profile sets replace the base ones
+fn apply_profile(manifest: &mut Manifest, profile: &str) -> Result<(), Box<dyn Error>> {
+    let mut found = false;
+    for package in &mut manifest.packages {
+        let Some(options) = package.metadata.as_mut().and_then(|m| m.rpm.as_mut()) else {
+            continue;
+        };
+        let Some(overrides) = options.profiles.as_mut().and_then(|p| p.remove(profile)) else {
+            continue;
+        };
+
+        let serde_json::Value::Object(overrides) = overrides else {
+            return Err(format!("rpm profile {profile} of {} isn't a table", package.name).into());
+        };
+
+        let mut merged = serde_json::to_value(&*options)?;
+        if let serde_json::Value::Object(ref mut merged) = merged {
+            merged.extend(overrides);
+        }
+
+        *options = serde_json::from_value(merged).map_err(|e| {
+            format!(
+                "invalid rpm profile {profile} of {}: {}",
+                package.name,
+                explain_unknown_field(&e)
+            )
+        })?;
+        found = true;
+    }
+
+    if !found {
+        return Err(format!("no package defines the rpm profile {profile}").into());
+    }
+
+    Ok(())
+}
+
+/// Make sure every configured symlink made it into the package, and wasn't shadowed by a file
+fn check_symlinks(
+    pkg: &rpm::Package,
+    symlinks: &BTreeMap<String, String>,
+) -> Result<(), Box<dyn Error>> {
+    let entries = pkg.metadata.get_file_entries()?;
+    for (link, target) in symlinks {
+        let entry = entries.iter().find(|e| e.path == Path::new(link));
+        if entry.is_none_or(|e| e.linkto != *target) {
+            return Err(format!("symlink {link} collides with a packaged file").into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the files in the package and its subpackages against the list of expected install
+/// paths
+fn check_expected_files(
+    packages: &[&rpm::Package],
+    expect_files: &PathBuf,
+) -> Result<(), Box<dyn Error>> {
+    let expected = fs::read_to_string(expect_files)?;
+    let expected: BTreeSet<PathBuf> = expected
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(PathBuf::from)
+        .collect();
+    // directories are left out, most of them are the ones the package owns
+    let mut actual = BTreeSet::new();
+    for package in packages {
+        let entries = package.metadata.get_file_entries()?;
+        actual.extend(
+            entries
+                .into_iter()
+                .filter(|e| !matches!(e.mode, rpm::FileMode::Dir { .. }))
+                .map(|e| e.path),
+        );
+    }
+
+    let mut problems = Vec::new();
+    for missing in expected.difference(&actual) {
+        problems.push(format!("missing file {}", missing.display()));
+    }
+    for unexpected in actual.difference(&expected) {
+        problems.push(format!("unexpected file {}", unexpected.display()));
+    }
+
+    if !problems.is_empty() {
+        return Err(format!(
+            "package contents don't match {}: {}",
+            expect_files.display(),
+            problems.join(", ")
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Run the cargo build and collect the messages it emits
+///
+/// Diagnostics are still rendered to stderr by cargo, only the JSON messages on stdout are captured.
+fn run_build(build: &mut Command) -> Result<Option<BuildOutput>, Box<dyn Error>> {
+    let mut child = build.stdout(std::process::Stdio::piped()).spawn()?;
+
+    let mut output = BuildOutput::default();
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or("failed to capture cargo output")?;
+    for line in BufReader::new(stdout).lines() {
+        let line = line?;
+        match serde_json::from_str(&line) {
+            Ok(BuildMessage::BuildScriptExecuted {
+                package_id,
+                out_dir,
+            }) => {
+                output.out_dirs.insert(package_id, out_dir);
+            }
+            Ok(BuildMessage::CompilerArtifact {
+                package_id,
+                target,
+                filenames,
+                executable,
+                features,
+            }) => {
+                output
+                    .features
+                    .entry(package_id.clone())
+                    .or_default()
+                    .extend(features);
+
+                let key = (package_id, target.name);
+                if target.kind.iter().any(|k| k == "example") {
+                    if let Some(executable) = executable {
+                        output.examples.insert(key, executable);
+                    }
+                    continue;
+                }
+
+                if let Some(executable) = executable {
+                    output.executables.insert(key.clone(), executable);
+                }
+                output.filenames.insert(key, filenames);
+            }
+            Ok(BuildMessage::Other) => {}
+            // not a message, pass it through
+            Err(_) => println!("{line}"),
+        }
+    }
+
+    if !child.wait()?.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(output))
+}
+
+/// Resolve the source path of an asset
+///
+/// Paths starting with `$OUT_DIR/` are relative to the `OUT_DIR` of the package's build script,
+/// everything else is relative to the crate directory.
+fn asset_source(
+    filename: &str,
+    crate_dir: &Path,
+    out_dir: Option<&PathBuf>,
+) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(filename) = filename.strip_prefix("$OUT_DIR/") {
+        let out_dir = out_dir.ok_or(format!(
+            "asset $OUT_DIR/{filename} requires the package to have a build script"
+        ))?;
+
+        Ok(out_dir.join(filename))
+    } else {
+        Ok(crate_dir.join(filename))
+    }
+}
+
+/// The files an asset installs and where, a `dest` ending in `/` installs them in that
+/// directory under their own names, which a glob in the file name of the source needs
+fn asset_files(
+    filename: &str,
+    dest: &str,
+    crate_dir: &Path,
+    out_dir: Option<&PathBuf>,
+) -> Result<Vec<(PathBuf, String)>, Box<dyn Error>> {
+    let source = asset_source(filename, crate_dir, out_dir)?;
+    let is_glob = source
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(['*', '?']));
+
+    let sources = if is_glob {
+        if !dest.ends_with('/') {
+            return Err(format!(
+                "asset {filename} is a glob, its dest {dest} needs to be a directory ending in /"
+            )
+            .into());
+        }
+
+        let sources = expand_glob(&source)?;
+        if sources.is_empty() {
+            return Err(format!("asset {filename} doesn't match any files").into());
+        }
+        sources
+    } else {
+        vec![source]
+    };
+
+    sources
+        .into_iter()
+        .map(|source| {
+            let dest = match dest.strip_suffix('/') {
+                Some(dir) => {
+                    let name = source
+                        .file_name()
+                        .ok_or(format!("invalid asset {filename}"))?;
+                    format!("{dir}/{}", name.to_string_lossy())
+                }
+                None => dest.to_owned(),
+            };
+
+            Ok((source, dest))
+        })
+        .collect()
+}
+
+/// The files matching the `*` and `?` wildcards in the file name of `pattern`, sorted by name
+fn expand_glob(pattern: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let dir = pattern.parent().ok_or("invalid glob")?;
+    let name = pattern.file_name().ok_or("invalid glob")?.to_string_lossy();
+    if dir.to_string_lossy().contains(['*', '?']) {
+        return Err(format!(
+            "invalid glob {}, wildcards are only supported in the file name",
+            pattern.display()
+        )
+        .into());
+    }
+
+    let mut regex = String::from("^");
+    for c in name.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    let regex = Regex::new(&regex)?;
+
+    let mut matches = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        // like a shell, wildcards don't match hidden files
+        if file_name.starts_with('.') && !name.starts_with('.') {
+            continue;
+        }
+
+        if regex.is_match(&file_name) {
+            matches.push(entry.path());
+        }
+    }
+    matches.sort();
+
+    Ok(matches)
+}
+
+#[derive(Clone, Copy)]
+enum DefaultAsset {
+    License,
+    Readme,
+}
+
+impl DefaultAsset {
+    /// The file name prefixes of the files of this kind, in upper case
+    fn prefixes(self) -> &'static [&'static str] {
+        match self {
+            DefaultAsset::License => &["LICENSE", "LICENCE", "COPYING"],
+            DefaultAsset::Readme => &["README"],
+        }
+    }
+}
+
+/// The license and readme files installed by `--defaults`, each kind from the package's
+/// directory or else the workspace root
+fn default_assets(
+    package_dir: &Path,
+    crate_dir: &Path,
+) -> io::Result<Vec<(PathBuf, DefaultAsset)>> {
+    let find = |dir: &Path, kind: DefaultAsset| -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_uppercase())
+                .unwrap_or_default();
+
+            if path.is_file() && kind.prefixes().iter().any(|p| name.starts_with(p)) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        Ok(paths)
+    };
+
+    let mut assets = Vec::new();
+    for kind in [DefaultAsset::License, DefaultAsset::Readme] {
+        let mut paths = find(package_dir, kind)?;
+        if paths.is_empty() {
+            paths = find(crate_dir, kind)?;
+        }
+        assets.extend(paths.into_iter().map(|p| (p, kind)));
+    }
+
+    Ok(assets)
+}
+
+/// An OpenPGP key as ASCII armored text, which is all the rpm crate can load
+///
+/// Keys exported without `--armor`, usually `.gpg` or `.pgp` files, are binary packets that are
+/// armored here, `block` is the kind of key named in the armor header, like `PRIVATE KEY BLOCK`.
+fn armored_key(key: &[u8], block: &str) -> Result<String, String> {
+    if let Ok(text) = std::str::from_utf8(key) {
+        if text.trim_start().starts_with("-----BEGIN PGP ") {
+            return Ok(text.to_owned());
+        }
+    }
+
+    // the tag byte every OpenPGP packet starts with has its high bit set
+    if key.first().is_none_or(|byte| byte & 0x80 == 0) {
+        return Err("not an ASCII armored or binary OpenPGP key".to_owned());
+    }
+
+    let mut armored = format!("-----BEGIN PGP {block}-----\n\n");
+    let encoded = BASE64_STANDARD.encode(key);
+    for (i, c) in encoded.chars().enumerate() {
+        if i > 0 && i % 64 == 0 {
+            armored.push('\n');
+        }
+        armored.push(c);
+    }
+    armored.push_str(&format!("\n-----END PGP {block}-----\n"));
+
+    Ok(armored)
+}
+
+fn load_signer(signing_key: &Path, passphrase: Option<&str>) -> Result<Signer, Box<dyn Error>> {
+    let signing_key = armored_key(&fs::read(signing_key)?, "PRIVATE KEY BLOCK")?;
+    let signer = Signer::load_from_asc(&signing_key)?;
+    Ok(match passphrase {
+        Some(passphrase) => signer.with_key_passphrase(passphrase),
+        None => signer,
+    })
+}
+
+/// Whether the primary key or any subkey of a private key is encrypted with a passphrase
+fn is_passphrase_protected(signing_key: &Path) -> Result<bool, Box<dyn Error>> {
+    let armored = armored_key(&fs::read(signing_key)?, "PRIVATE KEY BLOCK")?;
+    let (key, _) = SignedSecretKey::from_string(&armored)?;
+    Ok(key.primary_key.secret_params().is_encrypted()
+        || key
+            .secret_subkeys
+            .iter()
+            .any(|subkey| subkey.key.secret_params().is_encrypted()))
+}
+
+/// Make sure the key can be loaded and unlocked, so a bad key fails before building
+fn check_signing_key(signing_key: &Path, passphrase: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let invalid = |e: Box<dyn Error>| format!("invalid signing key {}: {e}", signing_key.display());
+    let protected = is_passphrase_protected(signing_key).map_err(invalid)?;
+    let signer = load_signer(signing_key, passphrase).map_err(invalid)?;
+
+    match passphrase {
+        _ if !protected => {}
+        None => {
+            return Err(format!(
+                "signing key {} is passphrase protected, give the passphrase with \
+                 --signing-key-passphrase or CARGO_MAKE_RPM_SIGNING_KEY_PASSPHRASE",
+                signing_key.display()
+            )
+            .into())
+        }
+        // a wrong passphrase only shows when the key is unlocked to sign
+        Some(_) => {
+            signer
+                .sign(io::empty(), rpm::Timestamp::now())
+                .map_err(|e| {
+                    format!(
+                        "signing key {} can't be unlocked with the passphrase: {e}",
+                        signing_key.display()
+                    )
+                })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sign the package with each key and write it as `file_name` in `dir`
+///
+/// The signature header only holds a single signature, so when there are several keys a copy
+/// signed with each one is written to a subdirectory named after the key file instead.
+fn write_signed(
+    rpm_pkg: &mut rpm::Package,
+    dir: &Path,
+    file_name: &str,
+    signing_keys: &[PathBuf],
+    options: &WriteOptions,
+    timings: &mut Timings,
+) -> Result<(), Box<dyn Error>> {
+    let write = |rpm_pkg: &rpm::Package,
+                 path: PathBuf,
+                 timings: &mut Timings|
+     -> Result<(), Box<dyn Error>> {
+        if options.no_clobber && path.exists() {
+            return Err(format!("{} already exists", path.display()).into());
+        }
+
+        let start = Instant::now();
+        rpm_pkg.write_file(&path)?;
+        set_mode(&path, options.file_mode)?;
+        timings.record("writing", start);
+        Ok(())
+    };
+
+    let sign = |rpm_pkg: &mut rpm::Package,
+                signing_key: &Path,
+                timings: &mut Timings|
+     -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        let signer = load_signer(signing_key, options.passphrase.as_deref())?;
+        match options.source_date {
+            Some(source_date) => rpm_pkg.sign_with_timestamp(signer, source_date)?,
+            None => rpm_pkg.sign(signer)?,
+        }
+        timings.record("signing", start);
+        Ok(())
+    };
+
+    match signing_keys {
+        [] => write(rpm_pkg, dir.join(file_name), timings)?,
+        [signing_key] => {
+            sign(rpm_pkg, signing_key, timings)?;
+            write(rpm_pkg, dir.join(file_name), timings)?;
+        }
+        signing_keys => {
+            let mut names = BTreeSet::new();
+            for signing_key in signing_keys {
+                let name = signing_key.file_stem().ok_or("invalid signing key path")?;
+                if !names.insert(name) {
+                    return Err(format!(
+                        "multiple signing keys are named {}",
+                        name.to_string_lossy()
+                    )
+                    .into());
+                }
+            }
+
+            for signing_key in signing_keys {
+                let dir = dir.join(signing_key.file_stem().unwrap());
+                fs::create_dir_all(&dir)?;
+                set_mode(&dir, options.dir_mode)?;
+                sign(rpm_pkg, signing_key, timings)?;
+                write(rpm_pkg, dir.join(file_name), timings)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// How the rpms are written
+#[derive(Debug, Default, Clone)]
+struct WriteOptions {
+    /// Fail instead of replacing existing rpms
+    no_clobber: bool,
+    /// Permissions of the directories the rpms are written to
+    dir_mode: Option<u32>,
+    /// Permissions of the rpms
+    file_mode: Option<u32>,
+    /// Passphrase unlocking the signing keys
+    passphrase: Option<String>,
+    /// Time the signatures are made at instead of now
+    source_date: Option<u32>,
+}
+
+impl WriteOptions {
+    fn new(args: &Cli) -> Self {
+        WriteOptions {
+            no_clobber: args.no_clobber && !args.force,
+            dir_mode: args.out_dir_mode,
+            file_mode: args.rpm_mode,
+            passphrase: args.signing_key_passphrase.clone(),
+            source_date: args.source_date,
+        }
+    }
+}
+
+/// Set the permissions of a file or directory, if they're configured
+fn set_mode(path: &Path, mode: Option<u32>) -> io::Result<()> {
+    match mode {
+        Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode)),
+        None => Ok(()),
+    }
+}
+
+/// Parse the octal permissions of the output, like `775`
+fn parse_output_mode(mode: &str) -> Result<u32, String> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or(format!("invalid mode '{mode}', expected octal like 775"))
+}
+
+/// Sign an existing rpm in place
+fn sign_after_build(
+    rpm_file: &Path,
+    signing_keys: &[PathBuf],
+    options: &WriteOptions,
+    public_keys: &[PathBuf],
+) -> Result<(), Box<dyn Error>> {
+    let mut rpm_pkg = rpm::Package::open(rpm_file)?;
+    let dir = rpm_file.parent().ok_or("invalid rpm path")?;
+    let file_name = rpm_file.file_name().ok_or("invalid rpm path")?;
+
+    write_signed(
+        &mut rpm_pkg,
+        dir,
+        &file_name.to_string_lossy(),
+        signing_keys,
+        options,
+        &mut Timings::default(),
+    )?;
+
+    verify_signatures(
+        &output_paths(dir, &file_name.to_string_lossy(), signing_keys),
+        public_keys,
+    )
+}
+
+/// Check that each rpm is signed by one of the public keys, by reading back what was written
+fn verify_signatures(rpms: &[PathBuf], public_keys: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    if public_keys.is_empty() {
+        return Ok(());
+    }
+
+    let mut verifiers = Vec::with_capacity(public_keys.len());
+    for public_key in public_keys {
+        let verifier = armored_key(&fs::read(public_key)?, "PUBLIC KEY BLOCK")
+            .and_then(|key| Verifier::load_from_asc(&key).map_err(|e| e.to_string()))
+            .map_err(|e| format!("invalid public key {}: {e}", public_key.display()))?;
+        verifiers.push(verifier);
+    }
+
+    for rpm in rpms {
+        let package = rpm::Package::open(rpm)?;
+        if !verifiers
+            .iter()
+            .any(|v| package.verify_signature(v.clone()).is_ok())
+        {
+            return Err(format!(
+                "{} isn't signed by any of the keys given with --verify-signature",
+                rpm.display()
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a package for the rpm `arch` can be installed on a `host` of another rpm arch.
+/// Arches of the same machine don't always share a prefix, like armhfp and arm-nofp, so they're
+/// compared by the machine their binaries are for when it's known
+fn runs_on(arch: &str, host: &str) -> bool {
+    match (elf::rpm_arch_machine(arch), elf::rpm_arch_machine(host)) {
+        _ if arch == "noarch" => true,
+        (Some(machine), Some(host_machine)) => machine == host_machine,
+        _ => arch == host,
+    }
+}
+
+/// The exit code of the `--test-install` script when the packages don't install
+const INSTALL_FAILED: i32 = 10;
+/// The exit code of the `--test-install` script when its first command can't be run,
+/// the ones after it exit with the codes that follow
+const COMMAND_FAILED: i32 = 11;
+
+/// Check that a written package installs, and that `commands` run once it is.
+/// Uses a podman container if podman is available, otherwise only `rpm -i --test` on the host.
+/// Returns `None` when the package can't be tested on the host
+fn test_install(
+    args: &Cli,
+    dir: &Path,
+    file_names: &[String],
+    signing_keys: &[PathBuf],
+    arch: &str,
+    commands: &[String],
+) -> Result<Option<InstallTest>, Box<dyn Error>> {
+    let paths: Vec<PathBuf> = file_names
+        .iter()
+        .map(|name| output_paths(dir, name, signing_keys).remove(0))
+        .collect();
+    let file_name = &file_names[0];
+    let host_arch = host_triple()
+        .and_then(|h| Triplet::from_str(&h).ok())
+        .map(|t| t.rpm_arch());
+    if host_arch.is_some_and(|h| !runs_on(arch, &h)) {
+        annotate(
+            args,
+            "warning",
+            &format!("not testing {file_name}, it isn't built for the host"),
+        );
+        return Ok(None);
+    }
+
+    let podman = Command::new("podman")
+        .arg("--version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+
+    let status = if podman {
+        let dir = paths[0]
+            .parent()
+            .ok_or("invalid rpm path")?
+            .canonicalize()?;
+        // exit codes 126 and 127 mean the shell couldn't run the command at all,
+        // anything else means it ran even if it doesn't know --version.
+        // The script exits with its own codes so a failed install and a command that doesn't
+        // run can be told apart from each other and from podman failing to start the container
+        let rpms: Vec<String> = paths
+            .iter()
+            .filter_map(|p| p.file_name())
+            .map(|name| format!("/rpms/{}", name.to_string_lossy()))
+            .collect();
+        let mut script = format!(
+            "dnf install -y -q --nogpgcheck {} || exit {INSTALL_FAILED}\n",
+            rpms.join(" ")
+        );
+        for (i, command) in commands.iter().enumerate() {
+            script.push_str(&format!(
+                "{command} --version >/dev/null || [ $? -lt 126 ] || exit {}\n",
+                COMMAND_FAILED + i as i32
+            ));
+        }
+
+        Command::new("podman")
+            .args(["run", "--rm", "-v"])
+            .arg(format!("{}:/rpms:ro,Z", dir.display()))
+            .args([&args.test_image, "sh", "-c", &script])
+            .stdin(std::process::Stdio::null())
+            .status()
+    } else {
+        Command::new("rpm")
+            .args(["-i", "--test"])
+            .args(&paths)
+            .stdin(std::process::Stdio::null())
+            .status()
+    };
+
+    match status {
+        Ok(status) if status.success() && podman => Ok(Some(InstallTest::Runs)),
+        Ok(status) if status.success() => Ok(Some(InstallTest::Installs)),
+        Ok(status) if !podman || status.code() == Some(INSTALL_FAILED) => {
+            Err(format!("{file_name} failed to install").into())
+        }
+        Ok(status) => match status.code().map(|code| code - COMMAND_FAILED) {
+            Some(i) if (0..commands.len() as i32).contains(&i) => Err(format!(
+                "{file_name} installs, but {} can't be run",
+                commands[i as usize]
+            )
+            .into()),
+            _ => Err(format!("podman couldn't run {} ({status})", args.test_image).into()),
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            Err("testing the install needs podman or rpm".into())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The paths `write_signed` writes the package to
+fn output_paths(dir: &Path, file_name: &str, signing_keys: &[PathBuf]) -> Vec<PathBuf> {
+    match signing_keys {
+        [] | [_] => vec![dir.join(file_name)],
+        signing_keys => signing_keys
+            .iter()
+            .filter_map(|k| k.file_stem())
+            .map(|stem| dir.join(stem).join(file_name))
+            .collect(),
+    }
+}
+
+/// A hash of everything that goes into a package, so it isn't rebuilt when nothing changed.
+/// It's stored in the output directory, so it uses sha256 rather than a hasher that can change
+/// between Rust releases
+struct InputHash(Sha256);
+
+impl InputHash {
+    fn new(args: &Cli, triplet: &Triplet, package: &Package) -> Self {
+        let mut hash = InputHash(Sha256::new());
+        hash.add(env!("CARGO_PKG_VERSION"));
+        hash.add(output_args(args));
+        hash.add(triplet.to_string());
+        hash.add(package);
+
+        hash
+    }
+
+    fn add_file(&mut self, path: &Path) -> io::Result<()> {
+        if path.is_file() {
+            self.add_bytes(&fs::read(path)?);
+        }
+
+        Ok(())
+    }
+
+    fn add(&mut self, value: impl Serialize) {
+        // every type added here serializes, maps are ordered so the JSON is deterministic
+        let json = serde_json::to_vec(&value).expect("inputs serialize to JSON");
+        self.add_bytes(&json);
+    }
+
+    /// Add `bytes`, prefixed with their length so consecutive inputs can't run together
+    fn add_bytes(&mut self, bytes: &[u8]) {
+        self.0.update((bytes.len() as u64).to_le_bytes());
+        self.0.update(bytes);
+    }
+
+    fn finish(self) -> String {
+        self.0
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+/// The command line options that change the rpms that are written, the others only report on,
+/// check or select the packages and don't make an up to date rpm stale
+fn output_args(args: &Cli) -> serde_json::Value {
+    serde_json::json!({
+        "cargo_args": args.cargo_args,
+        "compression": args.compression,
+        "signing_key": args.signing_key,
+        "rpm_profile": args.rpm_profile,
+        "defaults": args.defaults,
+        "license_format": args.license_format,
+        "allow_missing_license": args.allow_missing_license,
+        "set_version": args.set_version,
+        "set_release": args.set_release,
+        "dist": args.dist,
+        "version_suffix": args.version_suffix,
+        "vcs_commit": args.vcs_commit,
+        "vendor": args.vendor,
+        "build_host": args.build_host,
+        "source_date": args.source_date,
+        "meta": args.meta,
+        "no_default_bindir": args.no_default_bindir,
+        "max_size": args.max_size,
+        "buildroot": args.buildroot,
+        "name": args.name,
+        "license": args.license,
+        "description": args.description,
+        "arch": args.arch,
+        "arch_variant": args.arch_variant,
+        "out_dir_mode": args.out_dir_mode,
+        "rpm_mode": args.rpm_mode,
+        "auto_requires": args.auto_requires,
+        "auto_provides": args.auto_provides,
+    })
+}
+
+/// Wall clock time spent in each phase of packaging
+#[derive(Default)]
+struct Timings {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// Add the time elapsed since `start` to `phase`
+    fn record(&mut self, phase: &'static str, start: Instant) {
+        let elapsed = start.elapsed();
+        match self.phases.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+
+    fn print(&self, name: &str) {
+        eprintln!("timings for {name}:");
+        for (phase, duration) in &self.phases {
+            eprintln!("  {phase}: {duration:.2?}");
+        }
+    }
+}
+
+/// Scratch directory for files that only exist to be added to a package
+///
+/// The directory is created on first use and removed when dropped.
+struct Staging {
+    dir: PathBuf,
+    files: usize,
+    mtime: SystemTime,
+}
+
+impl Staging {
+    /// Stage files in `temp_dir`, or the system's temporary directory which respects `TMPDIR`.
+    /// The files are dated `mtime` so they don't change the rpm each time they're generated
+    fn new(temp_dir: Option<&Path>, mtime: SystemTime) -> Self {
+        let temp_dir = temp_dir.map_or_else(std::env::temp_dir, Path::to_path_buf);
+        Staging {
+            dir: temp_dir.join(format!("cargo-make-rpm-{}", std::process::id())),
+            files: 0,
+            mtime,
+        }
+    }
+
+    /// Write `contents` to a new file in the staging directory and return its path
+    fn add(&mut self, contents: &[u8]) -> io::Result<PathBuf> {
+        self.add_dated(contents, self.mtime)
+    }
+
+    /// Write `contents` to a new file like [`Staging::add`], dated `mtime`
+    fn add_dated(&mut self, contents: &[u8], mtime: SystemTime) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(self.files.to_string());
+        fs::write(&path, contents)?;
+        fs::File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(mtime)?;
+        self.files += 1;
+
+        Ok(path)
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The short hash of the commit checked out in `dir`
+fn git_head(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(dir)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Check that the binary at `path` has a `.note.gnu.build-id` section
+fn check_build_id(args: &Cli, path: &Path, level: CheckLevel) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let invalid = |e| format!("invalid ELF file {}: {e}", path.display());
+    let has_build_id = Elf::parse(&data)
+        .map_err(invalid)?
+        .is_some_and(|elf| elf.section(".note.gnu.build-id").is_some());
+
+    if has_build_id {
+        return Ok(());
+    }
+
+    let message = format!("{} has no GNU build-id", path.display());
+    match level {
+        CheckLevel::Warn => {
+            annotate(args, "warning", &message);
+            Ok(())
+        }
+        CheckLevel::Deny => Err(message),
+    }
+}
+
+/// Check that the binary at `path` was built for the target, in case cargo fell back to the host,
+/// and that it matches the arch of the package. A prebuilt binary with `--arch` is only checked
+/// against the arch, the target doesn't matter for it
+fn check_machine(path: &Path, triplet: Option<&Triplet>, arch: &str) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let invalid = |e| format!("invalid ELF file {}: {e}", path.display());
+    let Some(elf) = Elf::parse(&data).map_err(invalid)? else {
+        return Ok(());
+    };
+
+    let actual = elf::machine_name(elf.machine);
+    if let Some(triplet) = triplet {
+        if elf::arch_machine(triplet.cfg_arch()).is_some_and(|m| m != elf.machine) {
+            return Err(format!(
+                "{} is built for {actual}, not for the target {triplet}",
+                path.display()
+            ));
+        }
+    }
+
+    if arch == "noarch" {
+        return Err(format!(
+            "{} is built for {actual}, it can't be in a noarch package",
+            path.display()
+        ));
+    }
+
+    match elf::rpm_arch_machine(arch) {
+        Some(expected) if expected != elf.machine => Err(format!(
+            "{} is built for {actual}, but the package arch is {arch}",
+            path.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The shared libraries the binaries need, named like rpm's automatic dependencies
+/// (`libssl.so.3()(64bit)`)
+fn needed_libraries(binaries: &[&PathBuf]) -> Result<BTreeSet<String>, String> {
+    let mut needed = BTreeSet::new();
+    for path in binaries {
+        let data = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let invalid = |e| format!("invalid ELF file {}: {e}", path.display());
+        let Some(elf) = Elf::parse(&data).map_err(invalid)? else {
+            continue;
+        };
+
+        for soname in elf.needed().map_err(invalid)? {
+            needed.insert(if elf.is_64() {
+                format!("{soname}()(64bit)")
+            } else {
+                soname
+            });
+        }
+    }
+
+    Ok(needed)
+}
+
+/// The sonames of the shared libraries, or their file names when they don't have one like
+/// rust's cdylibs, named like rpm's automatic provides
+fn provided_libraries(libraries: &[&PathBuf]) -> Result<BTreeSet<String>, String> {
+    let mut provided = BTreeSet::new();
+    for path in libraries {
+        let data = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let invalid = |e| format!("invalid ELF file {}: {e}", path.display());
+        let Some(elf) = Elf::parse(&data).map_err(invalid)? else {
+            continue;
+        };
+
+        let soname = elf
+            .soname()
+            .map_err(invalid)?
+            .or_else(|| Some(path.file_name()?.to_string_lossy().into_owned()))
+            .ok_or("invalid library path")?;
+        provided.insert(if elf.is_64() {
+            format!("{soname}()(64bit)")
+        } else {
+            soname
+        });
+    }
+
+    Ok(provided)
+}
+
+/// The directory cargo builds the package in, the workspace's target directory or `target` in
+/// the package's directory when there's no workspace, like for `--standalone`
+fn target_dir(manifest: &Manifest, crate_dir: &Path) -> PathBuf {
+    match manifest.target_directory {
+        Some(ref target_directory) => PathBuf::from(target_directory),
+        None => crate_dir.join("target"),
+    }
+}
+
+/// The directory rpms are written to, `target/rpm` or `target/<triple>/rpm` when cross compiling
+fn rpm_dir(target_dir: &Path, target: Option<&str>) -> PathBuf {
+    let mut dir = target_dir.to_owned();
+    if let Some(target) = target {
+        dir.push(target);
+    }
+
+    dir.join("rpm")
+}
+
+/// The directory paths in the package's options are relative to, the workspace root for its
+/// members and the package's own directory for members outside of it, like path dependencies
+fn crate_dir(workspace_root: Option<&String>, package: &Package) -> PathBuf {
+    let package_dir = Path::new(&package.manifest_path).parent().unwrap();
+    match workspace_root {
+        Some(root) if package_dir.starts_with(root) => PathBuf::from(root),
+        _ => package_dir.to_owned(),
+    }
+}
+
+/// The keys the package is signed with, the ones on the command line replace the configured ones
+fn signing_keys(args: &Cli, package: &Package, crate_dir: &Path) -> Vec<PathBuf> {
+    let signing_keys = if args.signing_key.is_empty() {
+        package
+            .rpm_options()
+            .and_then(|r| r.signing_key.as_ref())
+            .map(OneOrMany::as_slice)
+            .unwrap_or_default()
+    } else {
+        &args.signing_key
+    };
+
+    signing_keys.iter().map(|k| crate_dir.join(k)).collect()
+}
+
+/// The directory the package is written to
+fn output_dir(args: &Cli, manifest: &Manifest, package: &Package, target: Option<&str>) -> PathBuf {
+    match args.out_dir {
+        Some(ref out_dir) => out_dir.clone(),
+        None => {
+            let crate_dir = crate_dir(manifest.workspace_root.as_ref(), package);
+            rpm_dir(&target_dir(manifest, &crate_dir), target)
+        }
+    }
+}
+
+/// The rpm arch of the package when built for `triplet`
+fn package_arch(args: &Cli, package: &Package, triplet: &Triplet) -> Result<String, String> {
+    if package.assets_only() {
+        return Ok("noarch".to_owned());
+    }
+
+    let options = package.rpm_options();
+    let arch = args
+        .arch
+        .clone()
+        .or_else(|| options.and_then(|r| r.arch.clone()))
+        .unwrap_or_else(|| triplet.rpm_arch());
+
+    match args
+        .arch_variant
+        .as_ref()
+        .or(options.and_then(|r| r.arch_variant.as_ref()))
+    {
+        Some(_) if arch == "noarch" => Ok(arch),
+        Some(variant) => Ok(format!("{arch}_{}", parse_arch_variant(variant)?)),
+        None => Ok(arch),
+    }
+}
+
+/// The payload compression of the package, `--compression` replaces the one in its metadata
+fn package_compression(args: &Cli, package: &Package) -> Compression {
+    args.compression.unwrap_or_else(|| {
+        package
+            .rpm_options()
+            .map(|r| r.compression)
+            .unwrap_or_default()
+    })
+}
+
+/// The version of the package, with the version options applied
+fn package_version(args: &Cli, package: &Package) -> String {
+    let mut version = args
+        .set_version
+        .clone()
+        .unwrap_or_else(|| package.version.clone());
+    if let Some(ref suffix) = args.version_suffix {
+        if !suffix.starts_with(['~', '^']) {
+            version.push('~');
+        }
+        version.push_str(suffix);
+    }
+
+    version
+}
+
+/// The release of the package when it isn't the default of 1, with the dist tag appended
+fn package_release(
+    args: &Cli,
+    package: &Package,
+    target: Option<&str>,
+) -> Result<Option<String>, String> {
+    let dist = args
+        .dist
+        .as_ref()
+        .or(package.rpm_options().and_then(|r| r.dist.as_ref()));
+    let dist = match dist.map(String::as_str) {
+        Some("auto") if target.is_some() => {
+            return Err("--dist auto only detects the dist tag of native builds".to_owned())
+        }
+        Some("auto") => Some(detect_dist()?),
+        Some(dist) => Some(dist.to_owned()),
+        None => None,
+    };
+
+    if args.set_release.is_none() && dist.is_none() {
+        return Ok(None);
+    }
+
+    let mut release = args.set_release.clone().unwrap_or_else(|| "1".to_owned());
+    if let Some(dist) = dist {
+        if !dist.starts_with('.') {
+            release.push('.');
+        }
+        release.push_str(&dist);
+    }
+
+    parse_rpm_version(&release).map(Some)
+}
+
+/// The dist tag of the host, from the `ID` and `VERSION_ID` of `/etc/os-release`
+fn detect_dist() -> Result<String, String> {
+    let os_release = fs::read_to_string("/etc/os-release")
+        .map_err(|e| format!("failed to read /etc/os-release: {e}"))?;
+    let field = |key: &str| {
+        os_release
+            .lines()
+            .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+            .map(|v| v.trim_matches('"'))
+    };
+
+    let version = field("VERSION_ID").unwrap_or_default();
+    let major = version.split('.').next().unwrap_or_default();
+    match field("ID") {
+        Some("fedora") if !major.is_empty() => Ok(format!(".fc{major}")),
+        Some("rhel" | "centos" | "rocky" | "almalinux" | "ol") if !major.is_empty() => {
+            Ok(format!(".el{major}"))
+        }
+        Some("amzn") if !major.is_empty() => Ok(format!(".amzn{major}")),
+        id => Err(format!(
+            "no known dist tag for {}, use --dist with the tag",
+            id.unwrap_or("this system")
+        )),
+    }
+}
+
+/// The license of the package, in the configured format
+fn package_license(args: &Cli, package: &Package) -> Result<String, Box<dyn Error>> {
+    let options = package.rpm_options();
+    let license: &str = match package.license {
+        Some(ref license) => license,
+        None if args.allow_missing_license => {
+            annotate(
+                args,
+                "warning",
+                &format!(
+                    "{} has no license, using LicenseRef-Proprietary",
+                    package.name
+                ),
+            );
+            "LicenseRef-Proprietary"
+        }
+        None => return Err("Missing license".into()),
+    };
+    let license_format = args
+        .license_format
+        .or(options.and_then(|r| r.license_format))
+        .unwrap_or_default();
+
+    Ok(match license_format {
+        LicenseFormat::Spdx => license.to_owned(),
+        LicenseFormat::Legacy => {
+            legacy_license(license, options.and_then(|r| r.license_map.as_ref()))
+        }
+    })
+}
+
+/// The summary of the package, its crate's description on a single line
+fn package_summary(package: &Package) -> Result<String, String> {
+    let description = package
+        .description
+        .as_deref()
+        .filter(|d| !d.trim().is_empty())
+        .ok_or(format!("Missing description in crate {}", package.name))?;
+
+    Ok(normalize_text(description)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// The long description of the package, from its `description_file` or else the crate's
+/// description when it spans several lines
+fn package_description(package: &Package, crate_dir: &Path) -> Result<Option<String>, String> {
+    match package
+        .rpm_options()
+        .and_then(|r| r.description_file.as_ref())
+    {
+        Some(description_file) => {
+            let path = crate_dir.join(description_file);
+            let description = fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read description_file {}: {e}", path.display()))?;
+            Ok(Some(normalize_text(&description)))
+        }
+        None => Ok(package
+            .description
+            .as_deref()
+            .map(normalize_text)
+            .filter(|d| d.contains('\n'))),
+    }
+}
+
+/// Text with `\n` line endings and without trailing whitespace, which some rpm tools show
+/// literally or choke on
+fn normalize_text(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+
+    lines.join("\n").trim_matches('\n').to_owned()
+}
+
+/// The file name of the rpm named `name`, with the release only when it isn't the default
+fn package_file_name(name: &str, version: &str, release: Option<&str>, arch: &str) -> String {
+    match release {
+        Some(release) => format!("{name}-{version}-{release}.{arch}.rpm"),
+        None => format!("{name}-{version}.{arch}.rpm"),
+    }
+}
+
+/// An asset's source file and how it's installed
+type AssetFile = (PathBuf, rpm::FileOptionsBuilder);
+
+/// The files the assets install, with their install paths and options
+fn asset_options(
+    assets: &[Asset],
+    crate_dir: &Path,
+    out_dir: Option<&PathBuf>,
+    staging: &mut Staging,
+    inputs: &mut InputHash,
+) -> Result<Vec<(String, AssetFile)>, Box<dyn Error>> {
+    let mut installed = Vec::new();
+    for asset in assets {
+        let (dest, mode) = match asset {
+            Asset::Short(_, dest, mode) => (dest, Some(mode)),
+            Asset::Table(AssetTable { dest, mode, .. }) => (dest, mode.as_ref()),
+        };
+
+        let files = match asset {
+            Asset::Short(filename, ..)
+            | Asset::Table(AssetTable {
+                source: Some(filename),
+                content: None,
+                ..
+            }) => asset_files(filename, dest, crate_dir, out_dir)?,
+            Asset::Table(AssetTable {
+                source: None,
+                content: Some(content),
+                ..
+            }) if !dest.ends_with('/') => {
+                vec![(staging.add(content.as_bytes())?, dest.clone())]
+            }
+            Asset::Table(AssetTable { content: None, .. })
+            | Asset::Table(AssetTable {
+                source: Some(_), ..
+            }) => {
+                return Err(format!("asset {dest} needs exactly one of source or content").into())
+            }
+            Asset::Table(_) => {
+                return Err(
+                    format!("asset {dest} has inline content, its dest needs a file name").into(),
+                )
+            }
+        };
+
+        for (mut filepath, mut dest) in files {
+            if let Asset::Table(AssetTable { gzip: true, .. }) = asset {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(&fs::read(&filepath)?)?;
+                let mtime = fs::metadata(&filepath)?.modified()?;
+                filepath = staging.add_dated(&encoder.finish()?, mtime)?;
+
+                if !dest.ends_with(".gz") {
+                    dest.push_str(".gz");
+                }
+            }
+
+            let mode = match mode {
+                Some(mode) => parse_mode(mode, &dest)?,
+                None if filepath.is_dir() => 0o755,
+                None => 0o644,
+            };
+
+            let mut file_options = FileOptions::new(&dest).mode(pad_permission(mode, &filepath)?);
+
+            match asset.marks(&dest)? {
+                (true, _) => file_options = file_options.is_doc(),
+                (_, true) => file_options = file_options.is_license(),
+                _ => {}
+            }
+
+            if let Asset::Table(AssetTable {
+                verify,
+                user,
+                group,
+                caps,
+                ..
+            }) = asset
+            {
+                if let Some(verify) = verify {
+                    file_options = file_options.verify(parse_verify(verify, &dest)?);
+                }
+
+                if let Some(user) = user {
+                    file_options = file_options.user(parse_owner(user, "user", &dest)?);
+                }
+
+                if let Some(group) = group {
+                    file_options = file_options.group(parse_owner(group, "group", &dest)?);
+                }
+
+                if let Some(caps) = caps {
+                    if filepath.is_dir() || mode & 0o111 == 0 {
+                        return Err(format!(
+                            "asset '{dest}' has caps but mode {mode:o} isn't executable"
+                        )
+                        .into());
+                    }
+
+                    file_options = file_options
+                        .caps(caps)
+                        .map_err(|e| format!("invalid caps '{caps}' for asset '{dest}': {e}"))?;
+                }
+            }
+
+            inputs.add_file(&filepath)?;
+            installed.push((dest, (filepath, file_options)));
+        }
+    }
+
+    Ok(installed)
+}
+
+/// Split the assets into groups of at most `max_size` bytes, in the order they're configured.
+/// Returns a single group when they all fit
+fn split_assets(
+    files: Vec<AssetFile>,
+    max_size: u64,
+) -> Result<Vec<Vec<AssetFile>>, Box<dyn Error>> {
+    let mut parts: Vec<Vec<_>> = Vec::new();
+    let mut part_size = 0;
+    for (path, options) in files {
+        let size = fs::metadata(&path)?.len();
+        if size > max_size {
+            return Err(format!(
+                "asset {} is larger than --max-size, {size} bytes",
+                path.display()
+            )
+            .into());
+        }
+
+        if parts.is_empty() || part_size + size > max_size {
+            parts.push(Vec::new());
+            part_size = 0;
+        }
+
+        part_size += size;
+        parts.last_mut().unwrap().push((path, options));
+    }
+
+    Ok(parts)
+}
+
+/// Parse a size in bytes, with an optional `K`, `M` or `G` suffix for powers of 1024
+fn parse_size(size: &str) -> Result<u64, String> {
+    let (digits, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => size.split_at(i),
+        None => (size, ""),
+    };
+
+    let unit = match unit.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => {
+            return Err(format!(
+                "invalid size '{size}', expected a number like 500M"
+            ))
+        }
+    };
+
+    digits
+        .parse::<u64>()
+        .ok()
+        .filter(|&n| n > 0)
+        .map(|n| n * unit)
+        .ok_or(format!(
+            "invalid size '{size}', expected a number like 500M"
+        ))
+}
+
+/// Whether the rpm of the package already exists in the output directory
+fn package_exists(
+    args: &Cli,
+    manifest: &Manifest,
+    triplet: &Triplet,
+    target: Option<&str>,
+    package: &Package,
+) -> Result<bool, String> {
+    let dir = output_dir(args, manifest, package, target);
+    let arch = package_arch(args, package, triplet)?;
+    let release = package_release(args, package, target)?;
+    let version = package_version(args, package);
+    let file_name = package_file_name(&package.name, &version, release.as_deref(), &arch);
+    let crate_dir = crate_dir(manifest.workspace_root.as_ref(), package);
+    let signing_keys = signing_keys(args, package, &crate_dir);
+
+    // how many parts and features the package has is only known once it's built, so the
+    // subpackages come from the list written along with them
+    let list = dir.join(".cache").join(format!("{file_name}.files"));
+    let file_names = match fs::read_to_string(&list) {
+        Ok(list) => list.lines().map(str::to_owned).collect(),
+        Err(_) => vec![file_name],
+    };
+
+    Ok(file_names
+        .iter()
+        .flat_map(|name| output_paths(&dir, name, &signing_keys))
+        .all(|p| p.exists()))
+}
+
+/// Build and write the rpm for a single package from the output of [`cargo_build`]
+pub fn build_package(
+    args: &Cli,
+    manifest: &Manifest,
+    triplet: &Triplet,
+    target: Option<&str>,
+    build_output: &BuildOutput,
+    package: &Package,
+) -> Result<BuiltPackage, Box<dyn Error>> {
+    let crate_dir = crate_dir(manifest.workspace_root.as_ref(), package);
+
+    let rpm_path = output_dir(args, manifest, package, target);
+    fs::create_dir_all(&rpm_path)?;
+    set_mode(&rpm_path, args.out_dir_mode)?;
+
+    let mut timings = Timings::default();
+    let start = Instant::now();
+    // generated files are dated like the manifest they come from, unless there's a source date
+    let staged_mtime = match args.source_date {
+        Some(source_date) => UNIX_EPOCH + Duration::from_secs(source_date.into()),
+        None => fs::metadata(&package.manifest_path)
+            .and_then(|m| m.modified())
+            .unwrap_or(UNIX_EPOCH),
+    };
+    let mut staging = Staging::new(args.temp_dir.as_deref(), staged_mtime);
+    let mut inputs = InputHash::new(args, triplet, package);
+    let options = package.rpm_options();
+    let arch = package_arch(args, package, triplet)?;
+
+    let compression = package_compression(args, package);
+
+    let license = package_license(args, package)?;
+    let version = package_version(args, package);
+    let summary = package_summary(package)?;
+    let mut rpm = rpm::PackageBuilder::new(&package.name, &version, &license, &arch, &summary)
+        .compression(rpm::CompressionType::from(compression));
+    if let Some(source_date) = args.source_date {
+        rpm = rpm.source_date(source_date);
+    }
+
+    let release = package_release(args, package, target)?;
+    if let Some(ref release) = release {
+        rpm = rpm.release(release);
+    }
+
+    if let Some(description_file) = options.and_then(|r| r.description_file.as_ref()) {
+        inputs.add_file(&crate_dir.join(description_file))?;
+    }
+
+    if let Some(description) = package_description(package, &crate_dir)? {
+        rpm = rpm.description(description);
+    }
+
+    let vendor = args
+        .vendor
+        .clone()
+        .unwrap_or_else(|| vendor(&package.authors));
+    if !vendor.is_empty() {
+        rpm = rpm.vendor(vendor);
+    }
+
+    if let Some(build_host) = args
+        .build_host
+        .as_ref()
+        .or(options.and_then(|r| r.build_host.as_ref()))
+    {
+        rpm = rpm.build_host(build_host);
+    }
+
+    if let Some(url) = package.url() {
+        rpm = rpm.url(url);
+    }
+
+    let commit = if args.vcs_commit {
+        let commit = git_head(&crate_dir);
+        if commit.is_none() {
+            annotate(
+                args,
+                "warning",
+                &format!(
+                    "{} is not in a git repository, not recording the commit",
+                    package.name
+                ),
+            );
+        }
+        commit
+    } else {
+        None
+    };
+
+    match (&package.repository, commit) {
+        (Some(repository), Some(commit)) => rpm = rpm.vcs(format!("git:{repository}#{commit}")),
+        (Some(repository), None) => rpm = rpm.vcs(format!("git:{repository}")),
+        (None, Some(_)) => annotate(
+            args,
+            "warning",
+            &format!(
+                "{} has no repository, not recording the commit",
+                package.name
+            ),
+        ),
+        (None, None) => {}
+    }
+
+    // the ELF files that get installed, and the shared libraries among them
+    let mut binaries = Vec::new();
+    let mut libraries = Vec::new();
+    let mut commands = Vec::new();
+    // the install paths of the examples and assets, for the directories the package owns
+    let mut dests = Vec::new();
+    // the assets are added once it's known whether they're split into parts
+    let mut asset_paths = Vec::new();
+    for (target, kind) in package.packaged_targets() {
+        let key = (package.id.clone(), target.name.clone());
+        match kind {
+            TargetKind::Bin => {
+                let Some(dest) = package.bin_dest(&target.name, !args.no_default_bindir) else {
+                    continue;
+                };
+                let options = FileOptions::new(&dest).mode(0o100755);
+
+                let is_ghost = package
+                    .rpm_options()
+                    .and_then(|r| r.ghost_bins.as_ref())
+                    .is_some_and(|g| g.contains(&target.name));
+
+                if is_ghost {
+                    rpm = rpm.with_file(staging.add(&[])?, options.is_ghost())?;
+                    continue;
+                }
+
+                let path = build_output.executables.get(&key).ok_or(format!(
+                    "cargo didn't report an executable for bin {}",
+                    target.name
+                ))?;
+
+                binaries.push(path);
+                commands.push(dest.clone());
+                dests.push(dest);
+                inputs.add_file(path)?;
+                rpm = rpm.with_file(path, options)?;
+            }
+            TargetKind::Cdylib | TargetKind::Dylib | TargetKind::Staticlib => {
+                let (extension, mode) = match kind {
+                    TargetKind::Staticlib => ("a", 0o100644),
+                    _ => ("so", 0o100755),
+                };
+
+                let path = build_output
+                    .filenames
+                    .get(&key)
+                    .into_iter()
+                    .flatten()
+                    .find(|f| f.extension().is_some_and(|e| e == extension))
+                    .ok_or(format!(
+                        "cargo didn't report a {} for target {}",
+                        kind.as_str(),
+                        target.name
+                    ))?;
+                let filename = path.file_name().ok_or("invalid library path")?;
+
+                if kind != TargetKind::Staticlib {
+                    binaries.push(path);
+                    libraries.push(path);
+                }
+
+                inputs.add_file(path)?;
+                rpm = rpm.with_file(
+                    path,
+                    FileOptions::new(format!(
+                        "{}/{}",
+                        triplet.libdir(),
+                        filename.to_string_lossy()
+                    ))
+                    .mode(mode),
+                )?;
+            }
+        }
+    }
+
+    let examples_dir = options
+        .and_then(|r| r.examples_dir.as_deref())
+        .unwrap_or("/usr/bin");
+    let examples_prefix = options
+        .and_then(|r| r.examples_prefix.clone())
+        .unwrap_or_else(|| format!("{}-", package.name));
+    for example in package.examples() {
+        let path = build_output
+            .examples
+            .get(&(package.id.clone(), example.clone()))
+            .ok_or(format!(
+                "cargo didn't report an executable for example {example}"
+            ))?;
+        let dest = format!(
+            "{}/{examples_prefix}{example}",
+            examples_dir.trim_end_matches('/')
+        );
+
+        binaries.push(path);
+        commands.push(dest.clone());
+        dests.push(dest.clone());
+        inputs.add_file(path)?;
+        rpm = rpm.with_file(path, FileOptions::new(dest).mode(0o100755))?;
+    }
+
+    let checked_triplet = args.arch.is_none().then_some(triplet);
+    for path in &binaries {
+        check_machine(path, checked_triplet, &arch)?;
+    }
+
+    if let Some(level) = args.check_build_id {
+        for path in &binaries {
+            check_build_id(args, path, level)?;
+        }
+    }
+
+    if args.auto_requires {
+        for soname in needed_libraries(&binaries)? {
+            rpm = rpm.requires(Dependency::any(soname));
+        }
+    }
+
+    if args.auto_provides {
+        for soname in provided_libraries(&libraries)? {
+            rpm = rpm.provides(Dependency::any(soname));
+        }
+    }
+
+    if let Some(options) = options {
+        let bins: Vec<&str> = package
+            .packaged_targets()
+            .into_iter()
+            .filter(|(_, kind)| *kind == TargetKind::Bin)
+            .map(|(target, _)| target.name.as_str())
+            .collect();
+        for bin in options.bins.iter().flat_map(BTreeMap::keys) {
+            if !bins.contains(&bin.as_str()) {
+                return Err(format!("bins.{bin} doesn't name a packaged binary").into());
+            }
+        }
+
+        if let Some(preinstall) = options.scriptlet(ScriptletKind::PreInstall, triplet, &bins)? {
+            rpm = rpm.pre_install_script(preinstall);
+        }
+
+        if let Some(postinstall) = options.scriptlet(ScriptletKind::PostInstall, triplet, &bins)? {
+            rpm = rpm.post_install_script(postinstall);
+        }
+
+        if let Some(preuninstall) =
+            options.scriptlet(ScriptletKind::PreUninstall, triplet, &bins)?
+        {
+            rpm = rpm.pre_uninstall_script(preuninstall);
+        }
+
+        if let Some(postuninstall) =
+            options.scriptlet(ScriptletKind::PostUninstall, triplet, &bins)?
+        {
+            rpm = rpm.post_uninstall_script(postuninstall);
+        }
+
+        let target_options = options.target_options(triplet)?;
+        let dependencies = options
+            .dependencies
+            .iter()
+            .chain(
+                target_options
+                    .iter()
+                    .filter_map(|t| t.dependencies.as_ref()),
+            )
+            .flatten();
+
+        for dep in dependencies {
+            rpm = rpm.requires(Dependency::any(dep));
+        }
+
+        if let Some(conflicts) = &options.conflicts {
+            for conflict in conflicts {
+                rpm = rpm.conflicts(Dependency::any(conflict));
+            }
+        }
+
+        if let Some(assets) = &options.assets {
+            let out_dir = build_output.out_dirs.get(&package.id);
+            for (dest, file) in
+                asset_options(assets, &crate_dir, out_dir, &mut staging, &mut inputs)?
+            {
+                dests.push(dest);
+                asset_paths.push(file);
+            }
+        }
+    }
+
+    // added like assets, with the mode each file has in the buildroot and owned by root
+    let staged = match args.buildroot {
+        Some(ref buildroot) => buildroot_files(buildroot)?,
+        None => Vec::new(),
+    };
+    for (path, dest) in staged {
+        let metadata = path.symlink_metadata()?;
+        let mode = (metadata.permissions().mode() & 0o7777) as u16;
+        inputs.add_file(&path)?;
+        inputs.add((&dest, mode));
+        dests.push(dest.clone());
+
+        if metadata.is_symlink() {
+            let target = fs::read_link(&path)?.to_string_lossy().into_owned();
+            inputs.add(&target);
+            rpm = rpm.with_file(
+                staging.add(target.as_bytes())?,
+                FileOptions::new(dest)
+                    .symlink(target)
+                    .mode(rpm::FileMode::symbolic_link(0o777)),
+            )?;
+        } else if metadata.is_dir() {
+            let options = FileOptions::new(dest).mode(pad_permission(mode, &path)?);
+            rpm = rpm.with_file(staging.add(&[])?, options)?;
+        } else {
+            let options = FileOptions::new(dest).mode(pad_permission(mode, &path)?);
+            asset_paths.push((path, options));
+        }
+    }
+
+    let has_assets = options
+        .and_then(|r| r.assets.as_ref())
+        .is_some_and(|a| !a.is_empty());
+    let installs_files = has_assets
+        || args.buildroot.is_some()
+        || options.is_some_and(|r| {
+            r.symlinks.is_some() || r.owned_dirs.is_some() || r.features.is_some()
+        });
+    if package.is_meta() && installs_files {
+        return Err(format!("{} is a meta package, it can't install files", package.name).into());
+    }
+
+    if args.defaults && !has_assets && !package.is_meta() {
+        let package_dir = Path::new(&package.manifest_path)
+            .parent()
+            .ok_or("invalid manifest path")?;
+
+        for (path, kind) in default_assets(package_dir, &crate_dir)? {
+            let name = path
+                .file_name()
+                .ok_or("invalid asset path")?
+                .to_string_lossy();
+            let (dest, options) = match kind {
+                DefaultAsset::License => {
+                    let dest = format!("/usr/share/licenses/{}/{name}", package.name);
+                    (dest.clone(), FileOptions::new(dest).is_license())
+                }
+                DefaultAsset::Readme => {
+                    let dest = format!("/usr/share/doc/{}/{name}", package.name);
+                    (dest.clone(), FileOptions::new(dest).is_doc())
+                }
+            };
+
+            inputs.add_file(&path)?;
+            dests.push(dest);
+            rpm = rpm.with_file(&path, options.mode(0o100644))?;
+        }
+    }
+
+    // added last, so a link that collides with a file keeps the file and is caught after building
+    let symlinks = options.and_then(|r| r.symlinks.as_ref());
+    for (link, target) in symlinks.into_iter().flatten() {
+        if !link.starts_with('/') || target.is_empty() {
+            return Err(
+                format!("symlink {link} -> {target} needs an absolute path and a target").into(),
+            );
+        }
+
+        dests.push(link.clone());
+        rpm = rpm.with_file(
+            staging.add(target.as_bytes())?,
+            FileOptions::new(link)
+                .symlink(target)
+                .mode(rpm::FileMode::symbolic_link(0o777)),
+        )?;
+    }
+
+    let configured_dirs = options.and_then(|r| r.owned_dirs.as_ref());
+    for dir in configured_dirs.into_iter().flatten() {
+        dests.push(dir.path().trim_end_matches('/').to_owned());
+        rpm = rpm.with_file(staging.add(&[])?, dir.file_options()?)?;
+    }
+
+    // so uninstalling removes them, files already installed at these paths are kept
+    for dir in owned_dirs(&package.name, &dests) {
+        rpm = rpm.with_file(staging.add(&[])?, FileOptions::new(dir).mode(0o040755))?;
+    }
+
+    // with --max-size, assets that don't fit in one rpm are all split into `<name>-part<n>`
+    // packages that the package requires, the binaries and other files stay in the package itself
+    let mut asset_parts = match args.max_size {
+        Some(max_size) => split_assets(asset_paths, max_size)?,
+        None => vec![asset_paths],
+    };
+    if asset_parts.len() == 1 {
+        for (path, options) in asset_parts.remove(0) {
+            rpm = rpm.with_file(path, options)?;
+        }
+    }
+
+    let full_version = format!("{version}-{}", release.as_deref().unwrap_or("1"));
+    let mut subpackages = Vec::with_capacity(asset_parts.len());
+    for (i, files) in asset_parts.into_iter().enumerate() {
+        let name = format!("{}-part{}", package.name, i + 1);
+        let mut part = rpm::PackageBuilder::new(
+            &name,
+            &version,
+            &license,
+            &arch,
+            &format!("{summary} (part {})", i + 1),
+        )
+        .compression(rpm::CompressionType::from(compression));
+        if let Some(ref release) = release {
+            part = part.release(release);
+        }
+        if let Some(source_date) = args.source_date {
+            part = part.source_date(source_date);
+        }
+
+        for (path, options) in files {
+            part = part.with_file(path, options)?;
+        }
+
+        rpm = rpm.requires(Dependency::eq(&name, &full_version));
+        subpackages.push((
+            package_file_name(&name, &version, release.as_deref(), &arch),
+            part,
+        ));
+    }
+
+    // the assets of enabled features go in `<name>-<feature>` packages that the package only
+    // recommends, so they're installed along with it by default but can be left out
+    let enabled = build_output.features.get(&package.id);
+    inputs.add(enabled);
+    let features = options.and_then(|r| r.features.as_ref());
+    for (feature, feature_options) in features.into_iter().flatten() {
+        if !package.features.contains_key(feature) {
+            return Err(
+                format!("{}: {feature} isn't a feature of the package", package.name).into(),
+            );
+        }
+
+        if !enabled.is_some_and(|f| f.contains(feature)) {
+            continue;
+        }
+
+        let name = format!("{}-{feature}", package.name);
+        let feature_summary = match feature_options.summary {
+            Some(ref summary) => summary.clone(),
+            None => format!("{summary} ({feature} feature)"),
+        };
+        let mut subpackage =
+            rpm::PackageBuilder::new(&name, &version, &license, &arch, &feature_summary)
+                .compression(rpm::CompressionType::from(compression))
+                .requires(Dependency::eq(&package.name, &full_version));
+        if let Some(ref release) = release {
+            subpackage = subpackage.release(release);
+        }
+        if let Some(source_date) = args.source_date {
+            subpackage = subpackage.source_date(source_date);
+        }
+
+        for dep in feature_options.dependencies.iter().flatten() {
+            subpackage = subpackage.requires(Dependency::any(dep));
+        }
+
+        let out_dir = build_output.out_dirs.get(&package.id);
+        let files = asset_options(
+            &feature_options.assets,
+            &crate_dir,
+            out_dir,
+            &mut staging,
+            &mut inputs,
+        )?;
+        let feature_dests: Vec<String> = files.iter().map(|(dest, _)| dest.clone()).collect();
+        for (_, (path, options)) in files {
+            subpackage = subpackage.with_file(path, options)?;
+        }
+
+        for dir in owned_dirs(&package.name, &feature_dests) {
+            subpackage =
+                subpackage.with_file(staging.add(&[])?, FileOptions::new(dir).mode(0o040755))?;
+        }
+
+        rpm = rpm.recommends(Dependency::eq(&name, &full_version));
+        subpackages.push((
+            package_file_name(&name, &version, release.as_deref(), &arch),
+            subpackage,
+        ));
+    }
+
+    let signing_keys = signing_keys(args, package, &crate_dir);
+    for signing_key in &signing_keys {
+        inputs.add_file(signing_key)?;
+    }
+
+    timings.record("reading files", start);
+
+    let file_name = package_file_name(&package.name, &version, release.as_deref(), &arch);
+    let file_names: Vec<String> = std::iter::once(file_name.clone())
+        .chain(subpackages.iter().map(|(name, _)| name.clone()))
+        .collect();
+    let hash = inputs.finish();
+    let cache = rpm_path.join(".cache").join(format!("{file_name}.hash"));
+    let paths: Vec<PathBuf> = file_names
+        .iter()
+        .flat_map(|name| output_paths(&rpm_path, name, &signing_keys))
+        .collect();
+    let up_to_date = fs::read_to_string(&cache).is_ok_and(|cached| cached == hash)
+        && paths.iter().all(|p| p.exists());
+    let built = |paths: Vec<PathBuf>, up_to_date, tested| BuiltPackage {
+        name: package.name.clone(),
+        version: version.clone(),
+        release: release.clone(),
+        arch: arch.clone(),
+        paths,
+        up_to_date,
+        tested,
+    };
+    let test = || {
+        if !args.test_install {
+            return Ok(None);
+        }
+
+        test_install(
+            args,
+            &rpm_path,
+            &file_names,
+            &signing_keys,
+            &arch,
+            &commands,
+        )
+    };
+
+    if up_to_date && !args.force {
+        for path in &paths {
+            set_mode(path, args.rpm_mode)?;
+        }
+        if let Some(ref expect_files) = args.expect_files {
+            // the copies signed with each key have the same files, so they can all be read
+            let written = paths
+                .iter()
+                .map(rpm::Package::open)
+                .collect::<Result<Vec<_>, _>>()?;
+            check_expected_files(&written.iter().collect::<Vec<_>>(), expect_files)?;
+        }
+        verify_signatures(&paths, &args.verify_signature)?;
+        let tested = test()?;
+        return Ok(built(paths, true, tested));
+    }
+
+    let start = Instant::now();
+    let mut rpm_pkg = rpm.build()?;
+    let mut subpackages = subpackages
+        .into_iter()
+        .map(|(file_name, subpackage)| Ok((file_name, subpackage.build()?)))
+        .collect::<Result<Vec<_>, rpm::Error>>()?;
+    timings.record("compressing", start);
+
+    if !args.allow_empty
+        && !package.is_meta()
+        && rpm_pkg
+            .metadata
+            .get_file_entries()
+            .unwrap_or_default()
+            .is_empty()
+    {
+        return Err(format!(
+            "{} doesn't contain any files, configure assets or use --allow-empty",
+            package.name
+        )
+        .into());
+    }
+
+    if let Some(symlinks) = symlinks {
+        check_symlinks(&rpm_pkg, symlinks)?;
+    }
+
+    if let Some(ref expect_files) = args.expect_files {
+        let packages: Vec<&rpm::Package> = std::iter::once(&rpm_pkg)
+            .chain(subpackages.iter().map(|(_, subpackage)| subpackage))
+            .collect();
+        check_expected_files(&packages, expect_files)?;
+    }
+
+    write_signed(
+        &mut rpm_pkg,
+        &rpm_path,
+        &file_name,
+        &signing_keys,
+        &WriteOptions::new(args),
+        &mut timings,
+    )?;
+
+    for (subpackage_file_name, subpackage) in &mut subpackages {
+        write_signed(
+            subpackage,
+            &rpm_path,
+            subpackage_file_name,
+            &signing_keys,
+            &WriteOptions::new(args),
+            &mut timings,
+        )?;
+    }
+
+    verify_signatures(&paths, &args.verify_signature)?;
+    fs::create_dir_all(rpm_path.join(".cache"))?;
+    fs::write(&cache, hash)?;
+    let list = rpm_path.join(".cache").join(format!("{file_name}.files"));
+    fs::write(list, file_names.join("\n") + "\n")?;
+
+    let tested = test()?;
+
+    if args.timings {
+        timings.print(&package.name);
+    }
+
+    Ok(built(paths, false, tested))
+}
+
+/// The vendor for a list of authors, only their names without the `<email>` part
+fn vendor(authors: &[String]) -> String {
+    let names: Vec<String> = authors
+        .iter()
+        .map(|author| {
+            let name = author.split('<').next().unwrap_or(author);
+            name.split_whitespace().collect::<Vec<_>>().join(" ")
+        })
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    names.join(", ")
+}
+
+/// Package a prebuilt binary with the metadata given on the command line, without cargo
+fn build_standalone(
+    args: &Cli,
+    host: Option<&str>,
+    binary: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let target = args.target.first().map(String::as_str);
+    if args.target.len() > 1 {
+        return Err("--standalone packages a single binary, give at most one --target".into());
+    }
+
+    let triple = target
+        .or(host)
+        .ok_or("failed to detect the host target, use --target")?;
+    let triplet = Triplet::from_str(triple)?;
+    if !binary.is_file() {
+        return Err(format!("{} is not a file", binary.display()).into());
+    }
+
+    let bin = binary
+        .file_name()
+        .ok_or("invalid binary path")?
+        .to_string_lossy()
+        .into_owned();
+    let name = args.name.clone().ok_or("--standalone needs --name")?;
+
+    // the package stands in for a crate in the current directory, so it's written to target/rpm
+    let package = Package {
+        id: name.clone(),
+        name,
+        version: args
+            .set_version
+            .clone()
+            .ok_or("--standalone needs --set-version")?,
+        license: args.license.clone(),
+        description: args.description.clone(),
+        authors: Vec::new(),
+        targets: vec![Target {
+            name: bin.clone(),
+            kind: vec!["bin".to_owned()],
+        }],
+        manifest_path: std::env::current_dir()?
+            .join("Cargo.toml")
+            .to_string_lossy()
+            .into_owned(),
+        metadata: None,
+        homepage: None,
+        repository: None,
+        features: BTreeMap::new(),
+    };
+
+    let mut build_output = BuildOutput::default();
+    build_output
+        .executables
+        .insert((package.id.clone(), bin), binary.to_owned());
+
+    let manifest = Manifest::default();
+    let built = build_package(args, &manifest, &triplet, target, &build_output, &package)?;
+    print_status(&built);
+    Ok(built.paths)
+}
+
+/// Tell whether a package was up to date and how it was tested, which [`build_package`] leaves
+/// to its caller
+fn print_status(built: &BuiltPackage) {
+    let Some(file_name) = built.paths.first().and_then(|p| p.file_name()) else {
+        return;
+    };
+    let file_name = file_name.to_string_lossy();
+
+    if built.up_to_date {
+        eprintln!("{file_name} is up to date");
+    }
+
+    match built.tested {
+        Some(InstallTest::Installs) => eprintln!("{file_name} installs cleanly"),
+        Some(InstallTest::Runs) => eprintln!("{file_name} installs cleanly and its binaries run"),
+        None => {}
+    }
+}
+
+/// Write the report and bundle of the rpms that were written, if they were asked for
+fn write_outputs(args: &Cli, written: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    if args.report_json.is_none() && args.bundle.is_none() {
+        return Ok(());
+    }
+
+    let report = Report::new(written)?;
+    if let Some(ref path) = args.report_json {
+        report.write(path)?;
+    }
+
+    if let Some(ref path) = args.bundle {
+        write_bundle(path, &report)?;
+    }
+
+    Ok(())
+}
+
+/// The host triple reported by rustc
+pub fn host_triple() -> Option<String> {
+    let report = Command::new("rustc")
+        .args(["--version", "--verbose"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .ok()?
+        .wait_with_output()
+        .ok()?
+        .stdout;
+    let report = String::from_utf8_lossy(&report);
+    let regex = Regex::new(r"host: (.*)").unwrap();
+
+    Some(regex.captures(&report)?.get(1)?.as_str().to_string())
+}
+
+/// Read the workspace with `cargo metadata` and apply the workspace fields, `--meta` and
+/// `--rpm-profile` to its packages
+pub fn load_manifest(args: &Cli) -> Result<Manifest, Box<dyn Error>> {
+    let metadata = Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::inherit())
+        .spawn()?
+        .wait_with_output()?
+        .stdout;
+
+    let metadata: serde_json::Value = serde_json::from_slice(&metadata)?;
+    check_rpm_options(&metadata)?;
+    let mut manifest: Manifest = serde_json::from_value(metadata)?;
+    inherit_workspace_fields(&mut manifest)?;
+    if args.meta {
+        for package in &mut manifest.packages {
+            let metadata = package.metadata.get_or_insert(Metadata { rpm: None });
+            let options = match metadata.rpm {
+                Some(ref mut options) => options,
+                None => metadata
+                    .rpm
+                    .insert(serde_json::from_value(serde_json::json!({}))?),
+            };
+            options.meta = true;
+        }
+    }
+
+    if let Some(ref profile) = args.rpm_profile {
+        apply_profile(&mut manifest, profile)?;
+    }
+
+    // after the profile, so one that replaces the assets still gets the ones of the file
+    load_assets_files(&mut manifest)?;
+
+    Ok(manifest)
+}
+
+/// Build the targets of the packages that get installed in a single `cargo build`, which is run
+/// again up to `--retries` times when it fails
+pub fn cargo_build(
+    args: &Cli,
+    target: Option<&str>,
+    packages: &[&Package],
+) -> Result<BuildOutput, Box<dyn Error>> {
+    let built: Vec<&&Package> = packages.iter().filter(|p| !p.assets_only()).collect();
+    if built.is_empty() {
+        return Ok(BuildOutput::default());
+    }
+
+    let mut build = Command::new("cargo");
+    build.args(["build", "--release"]);
+
+    if let Some(target) = target {
+        build.args(["--target", target]);
+    }
+
+    for package in &built {
+        build.args(["-p", &package.name]);
+    }
+
+    for package in &built {
+        build.args(package.build_args());
+    }
+
+    build.args(&args.cargo_args);
+    build.arg("--message-format=json-render-diagnostics");
+    let start = Instant::now();
+    let mut retries = 0;
+    let build_output = loop {
+        if let Some(build_output) = run_build(&mut build)? {
+            break build_output;
+        }
+
+        if retries == args.retries {
+            return Err("cargo build failed".into());
+        }
+
+        // 2, 4, 8... seconds, so a registry that's briefly unavailable has time to recover
+        retries += 1;
+        let delay = Duration::from_secs(1 << retries.min(5));
+        annotate(
+            args,
+            "warning",
+            &format!(
+                "cargo build failed, retrying in {}s ({retries}/{})",
+                delay.as_secs(),
+                args.retries
+            ),
+        );
+        std::thread::sleep(delay);
+    };
+    if args.timings {
+        eprintln!("cargo build: {:.2?}", start.elapsed());
+    }
+
+    Ok(build_output)
+}
+
+/// The packages that failed with `--keep-going`, each already reported when it failed
+struct FailedPackages(Vec<String>);
+
+impl std::fmt::Display for FailedPackages {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to package {} package(s): {}",
+            self.0.len(),
+            self.0.join(", ")
+        )
+    }
+}
+
+// shown by main like the string errors
+impl std::fmt::Debug for FailedPackages {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self.to_string())
+    }
+}
+
+impl Error for FailedPackages {}
+
+/// Package the workspace in the current directory the way the command line options say
+pub fn run(args: &Cli) -> Result<(), Box<dyn Error>> {
+    let result = run_with(args);
+    // the error is printed by main too, as a workflow command it also shows up on the run.
+    // Packages that failed with --keep-going were already annotated one by one
+    if let Err(ref err) = result {
+        if args.annotations() == Annotations::Github && !err.is::<FailedPackages>() {
+            annotate(args, "error", &err.to_string());
+        }
+    }
+
+    result
+}
+
+fn run_with(args: &Cli) -> Result<(), Box<dyn Error>> {
+    if let Some(ref rpm_file) = args.sign_after_build {
+        let signing_keys: Vec<PathBuf> = args.signing_key.iter().map(PathBuf::from).collect();
+        for signing_key in &signing_keys {
+            check_signing_key(signing_key, args.signing_key_passphrase.as_deref())?;
+        }
+        return sign_after_build(
+            rpm_file,
+            &signing_keys,
+            // the rpm is replaced in place, so only the signing options apply
+            &WriteOptions {
+                passphrase: args.signing_key_passphrase.clone(),
+                source_date: args.source_date,
+                ..WriteOptions::default()
+            },
+            &args.verify_signature,
+        );
+    }
+
+    if let [ref old, ref new] = args.diff[..] {
+        let differences = diff::diff(old, new)?;
+        if differences.is_empty() {
+            return Ok(());
+        }
+
+        println!("--- {}\n+++ {}", old.display(), new.display());
+        for line in differences {
+            println!("{line}");
+        }
+        return Err("the packages differ".into());
+    }
+
+    let host = host_triple();
+    if args.show_target_info {
+        println!("host: {}", host.as_deref().unwrap_or("unknown"));
+        let targets = if args.target.is_empty() {
+            host.iter().collect::<Vec<_>>()
+        } else {
+            args.target.iter().collect()
+        };
+
+        for target in targets {
+            let triplet = Triplet::from_str(target)?;
+            println!("target: {triplet}");
+            println!("  arch: {}", triplet.arch);
+            println!("  vendor: {}", triplet.vendor);
+            println!("  os: {}", triplet.os);
+            println!("  libc: {}", triplet.libc.as_deref().unwrap_or("none"));
+            println!("rpm arch: {}", triplet.rpm_arch());
+        }
+        return Ok(());
+    }
+
+    if let Some(ref binary) = args.standalone {
+        for signing_key in &args.signing_key {
+            check_signing_key(
+                Path::new(signing_key),
+                args.signing_key_passphrase.as_deref(),
+            )?;
+        }
+        let written = build_standalone(args, host.as_deref(), binary)?;
+        return write_outputs(args, &written);
+    }
+
+    let mut manifest = load_manifest(args)?;
+    let selected = match args.package {
+        Some(ref spec) => Some(select_package(&manifest, spec)?),
+        None => None,
+    };
+
+    if manifest.packages.is_empty() {
+        return Err("nothing to package, cargo metadata didn't report any packages".into());
+    }
+
+    let mut skipped = Vec::new();
+    // the packages are taken out so the rest of the manifest can still be passed along
+    let packages: Vec<Package> = std::mem::take(&mut manifest.packages)
+        .into_iter()
+        .filter(|p| selected.as_ref().is_none_or(|id| &p.id == id))
+        .filter(|p| {
+            let packaged = p.has_packaged_targets();
+            if !packaged {
+                if args.verbose {
+                    let message = format!("skipping {}, it {}", p.name, p.skip_reason());
+                    annotate(args, "notice", &message);
+                }
+                skipped.push(format!("{} {}", p.name, p.skip_reason()));
+            }
+            packaged
+        })
+        .collect();
+
+    if packages.is_empty() {
+        return Err(format!("nothing to package: {}", skipped.join("; ")).into());
+    }
+
+    if args.buildroot.is_some() && packages.len() > 1 {
+        return Err(
+            "--buildroot is packaged into a single member, select it with --package".into(),
+        );
+    }
+
+    // the targets on the command line replace the ones declared in the metadata, members that
+    // don't declare any are built for the host like they would be on their own
+    let targets: Vec<Option<String>> = if !args.target.is_empty() {
+        args.target.iter().cloned().map(Some).collect()
+    } else {
+        let declared: BTreeSet<&String> = packages
+            .iter()
+            .filter_map(|p| p.rpm_options().and_then(|r| r.targets.as_ref()))
+            .flatten()
+            .collect();
+        let undeclared = packages
+            .iter()
+            .any(|p| p.rpm_options().and_then(|r| r.targets.as_ref()).is_none());
+
+        let mut targets: Vec<Option<String>> = declared.into_iter().cloned().map(Some).collect();
+        if undeclared {
+            targets.insert(0, None);
+        }
+        targets
+    };
+
+    for package in &packages {
+        package
+            .check_install_names()
+            .map_err(|e| format!("{}: {e}", package.name))?;
+        if let Some(options) = package.rpm_options() {
+            options
+                .check_arches()
+                .map_err(|e| format!("{}: {e}", package.name))?;
+        }
+    }
+
+    let signing_keys: BTreeSet<PathBuf> = packages
+        .iter()
+        .flat_map(|p| signing_keys(args, p, &crate_dir(manifest.workspace_root.as_ref(), p)))
+        .collect();
+    for signing_key in &signing_keys {
+        check_signing_key(signing_key, args.signing_key_passphrase.as_deref())?;
+    }
+
+    let mut written = Vec::new();
+    let mut failures = Vec::new();
+    let mut printed_spec = false;
+    for target in &targets {
+        let triple = target
+            .as_ref()
+            .or(host.as_ref())
+            .ok_or("failed to detect the host target, use --target")?;
+        let triplet = Triplet::from_str(triple)?;
+
+        if triplet.os != "linux" {
+            annotate(args, "warning", "You are creating for your current OS, not for Linux. Use --target to cross compile for a Linux target.");
+        }
+
+        // packages that declare their targets are only built for those, the others for the host
+        let packages: Vec<&Package> = packages
+            .iter()
+            .filter(|p| {
+                if !args.target.is_empty() {
+                    return true;
+                }
+                match p.rpm_options().and_then(|r| r.targets.as_ref()) {
+                    Some(declared) => target.as_ref().is_some_and(|t| declared.contains(t)),
+                    None => target.is_none(),
+                }
+            })
+            .filter(|p| {
+                let arch = triplet.rpm_arch();
+                let builds = p.rpm_options().is_none_or(|r| r.builds_for(&arch));
+                if !builds && args.verbose {
+                    let message = format!("skipping {}, it isn't built for {arch}", p.name);
+                    annotate(args, "notice", &message);
+                }
+                builds
+            })
+            .collect();
+
+        if args.print_spec {
+            for package in packages {
+                let spec = spec::spec(args, &manifest, &triplet, target.as_deref(), package)?;
+                if printed_spec {
+                    println!();
+                }
+                print!("{spec}");
+                printed_spec = true;
+            }
+            continue;
+        }
+
+        let packages: Vec<&Package> = if args.skip_existing {
+            let mut missing = Vec::new();
+            for package in packages {
+                if package_exists(args, &manifest, &triplet, target.as_deref(), package)? {
+                    let message = format!("skipping {}, its rpm already exists", package.name);
+                    annotate(args, "notice", &message);
+                } else {
+                    missing.push(package);
+                }
+            }
+            missing
+        } else {
+            packages
+        };
+
+        if packages.is_empty() {
+            continue;
+        }
+
+        let build_output = cargo_build(args, target.as_deref(), &packages)?;
+        for package in packages {
+            match build_package(
+                args,
+                &manifest,
+                &triplet,
+                target.as_deref(),
+                &build_output,
+                package,
+            ) {
+                Ok(built) => {
+                    print_status(&built);
+                    written.extend(built.paths);
+                }
+                Err(err) if !args.keep_going => return Err(err),
+                Err(err) => {
+                    let name = if targets.len() > 1 {
+                        format!("{} ({triple})", package.name)
+                    } else {
+                        package.name.clone()
+                    };
+                    annotate(args, "error", &format!("failed to package {name}: {err}"));
+                    failures.push(name);
+                }
+            }
+        }
+    }
+
+    // written even when some packages failed, so the ones that succeeded can still be used
+    write_outputs(args, &written)?;
+
+    if !failures.is_empty() {
+        return Err(FailedPackages(failures).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(manifest_path: &str) -> Package {
+        serde_json::from_value(serde_json::json!({
+            "id": "a 0.1.0",
+            "name": "a",
+            "version": "0.1.0",
+            "authors": [],
+            "targets": [],
+            "manifest_path": manifest_path,
+        }))
+        .unwrap()
+    }
+
+    /// A scratch directory for a test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("cargo-make-rpm-test-{test}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Build the package `foo` with the assets configured by the `rpm` metadata in `dir`, with
+    /// the command line `args`, and return the rpms it wrote to `dir/out`
+    fn build_assets(dir: &Path, rpm: serde_json::Value, args: &[&str]) -> Vec<PathBuf> {
+        try_build_assets(dir, rpm, args).unwrap()
+    }
+
+    /// [`build_assets`] for packages that are expected to fail
+    fn try_build_assets(
+        dir: &Path,
+        rpm: serde_json::Value,
+        args: &[&str],
+    ) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        try_build(dir, rpm, &[], "x86_64-unknown-linux-gnu", args)
+    }
+
+    /// Build the package `foo` with the `bins` for `triple`, each a script standing in for the
+    /// executable cargo would have built, and return the rpms it wrote to `dir/out`
+    fn try_build(
+        dir: &Path,
+        rpm: serde_json::Value,
+        bins: &[&str],
+        triple: &str,
+        args: &[&str],
+    ) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let package = foo(dir, rpm, bins);
+
+        let mut build_output = BuildOutput::default();
+        for bin in bins {
+            let executable = dir.join("build").join(bin);
+            fs::create_dir_all(dir.join("build")).unwrap();
+            fs::write(&executable, format!("#!/bin/sh\necho {bin}\n")).unwrap();
+            let key = (package.id.clone(), (*bin).to_owned());
+            build_output.executables.insert(key, executable);
+        }
+
+        build_package(
+            &foo_cli(dir, args),
+            &Manifest::default(),
+            &Triplet::from_str(triple).unwrap(),
+            None,
+            &build_output,
+            &package,
+        )
+        .map(|built| built.paths)
+    }
+
+    /// The package `foo` that [`try_build`] builds from `dir`
+    fn foo(dir: &Path, rpm: serde_json::Value, bins: &[&str]) -> Package {
+        let manifest_path = dir.join("Cargo.toml");
+        if !manifest_path.exists() {
+            fs::write(&manifest_path, "").unwrap();
+        }
+
+        let targets: Vec<_> = bins
+            .iter()
+            .map(|bin| serde_json::json!({ "name": bin, "kind": ["bin"] }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "id": "foo 0.1.0",
+            "name": "foo",
+            "version": "0.1.0",
+            "license": "MIT",
+            "description": "Test package",
+            "authors": [],
+            "targets": targets,
+            "manifest_path": manifest_path,
+            "metadata": { "rpm": rpm },
+        }))
+        .unwrap()
+    }
+
+    /// The command line [`try_build`] builds `foo` with, writing to `dir/out`
+    fn foo_cli(dir: &Path, args: &[&str]) -> Cli {
+        let out_dir = dir.join("out");
+        let mut cli = vec!["cargo-make-rpm", "--out-dir", out_dir.to_str().unwrap()];
+        cli.extend(args);
+        Cli::parse_from(cli)
+    }
+
+    #[test]
+    fn crate_dir_of_members() {
+        let root = "/work/ws".to_owned();
+        let member = package("/work/ws/crates/a/Cargo.toml");
+        assert_eq!(crate_dir(Some(&root), &member), Path::new("/work/ws"));
+
+        let root_package = package("/work/ws/Cargo.toml");
+        assert_eq!(crate_dir(Some(&root), &root_package), Path::new("/work/ws"));
+    }
+
+    #[test]
+    fn workspace_fields_of_members() {
+        let dir = TempDir::new("workspace-fields");
+        let workspace = r#"
+[workspace]
+members = ["a", "b"]
+
+[workspace.package]
+license = "MIT OR Apache-2.0"
+authors = ["Ferris <ferris@example.com>"]
+"#;
+        fs::write(dir.0.join("Cargo.toml"), workspace).unwrap();
+
+        let inheriting = package(dir.0.join("a/Cargo.toml").to_str().unwrap());
+        let mut own = package(dir.0.join("b/Cargo.toml").to_str().unwrap());
+        own.license = Some("GPL-3.0-only".to_owned());
+        let mut manifest = Manifest {
+            packages: vec![inheriting, own],
+            workspace_root: Some(dir.0.to_string_lossy().into_owned()),
+            ..Manifest::default()
+        };
+        inherit_workspace_fields(&mut manifest).unwrap();
+
+        let [inheriting, own] = &manifest.packages[..] else {
+            panic!("expected two packages");
+        };
+        assert_eq!(inheriting.license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(inheriting.authors, ["Ferris <ferris@example.com>"]);
+        // fields the member sets itself are kept
+        assert_eq!(own.license.as_deref(), Some("GPL-3.0-only"));
+        assert_eq!(own.authors, ["Ferris <ferris@example.com>"]);
+    }
+
+    #[test]
+    fn rpm_dir_with_and_without_target() {
+        let target_dir = Path::new("/work/ws/target");
+        // no empty segment for a missing target, like `target//rpm`
+        assert_eq!(
+            rpm_dir(target_dir, None).to_str(),
+            Some("/work/ws/target/rpm")
+        );
+        assert_eq!(
+            rpm_dir(target_dir, Some("aarch64-unknown-linux-gnu")).to_str(),
+            Some("/work/ws/target/aarch64-unknown-linux-gnu/rpm")
+        );
+    }
+
+    #[test]
+    fn output_dir_in_target_directory() {
+        let args = Cli::default();
+        let member = package("/work/ws/crates/a/Cargo.toml");
+        let mut manifest = Manifest {
+            workspace_root: Some("/work/ws".to_owned()),
+            target_directory: Some("/cache/target".to_owned()),
+            ..Manifest::default()
+        };
+        assert_eq!(
+            output_dir(&args, &manifest, &member, None),
+            Path::new("/cache/target/rpm")
+        );
+        assert_eq!(
+            output_dir(&args, &manifest, &member, Some("aarch64-unknown-linux-gnu")),
+            Path::new("/cache/target/aarch64-unknown-linux-gnu/rpm")
+        );
+
+        // without a target directory, like for --standalone, it's `target` in the crate directory
+        manifest.target_directory = None;
+        assert_eq!(
+            output_dir(&args, &manifest, &member, None),
+            Path::new("/work/ws/target/rpm")
+        );
+    }
+
+    #[test]
+    fn crate_dir_outside_workspace_root() {
+        let root = "/work/ws".to_owned();
+        let outside = package("/work/vendor/a/Cargo.toml");
+        assert_eq!(
+            crate_dir(Some(&root), &outside),
+            Path::new("/work/vendor/a")
+        );
+
+        // a sibling whose name starts with the root's isn't inside it
+        let sibling = package("/work/ws-extra/a/Cargo.toml");
+        assert_eq!(
+            crate_dir(Some(&root), &sibling),
+            Path::new("/work/ws-extra/a")
+        );
+    }
+
+    #[test]
+    fn normalized_text() {
+        assert_eq!(
+            normalize_text("one  \r\ntwo\t\rthree\n\n"),
+            "one\ntwo\nthree"
+        );
+        assert_eq!(
+            normalize_text("\n\nparagraph\n\n  indented\n"),
+            "paragraph\n\n  indented"
+        );
+    }
+
+    #[test]
+    fn multi_line_summary() {
+        let mut package = package("/work/a/Cargo.toml");
+        package.description = Some("A tool\r\n  that does things  \n".to_owned());
+        assert_eq!(
+            package_summary(&package).unwrap(),
+            "A tool that does things"
+        );
+
+        package.description = Some(" \n ".to_owned());
+        assert!(package_summary(&package).is_err());
+    }
+
+    #[test]
+    fn armored_keys() {
+        let armored =
+            "-----BEGIN PGP PUBLIC KEY BLOCK-----\n\nxjMEZ\n-----END PGP PUBLIC KEY BLOCK-----\n";
+        assert_eq!(
+            armored_key(armored.as_bytes(), "PUBLIC KEY BLOCK").unwrap(),
+            armored
+        );
+
+        let binary = [0x99; 100];
+        let key = armored_key(&binary, "PUBLIC KEY BLOCK").unwrap();
+        assert!(key.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\nmZmZ"));
+        assert!(key.ends_with("\n-----END PGP PUBLIC KEY BLOCK-----\n"));
+        assert!(key.lines().all(|line| line.len() <= 64));
+
+        assert!(armored_key(b"not a key", "PUBLIC KEY BLOCK").is_err());
+    }
+
+    #[test]
+    fn crate_dir_without_workspace() {
+        let package = package("/work/a/Cargo.toml");
+        assert_eq!(crate_dir(None, &package), Path::new("/work/a"));
+    }
+
+    #[test]
+    fn owned_dirs_of_assets() {
+        let dests = [
+            "/etc/foo-daemon/conf.d/a.conf".to_owned(),
+            "/usr/share/foo/data/b.json".to_owned(),
+            "/usr/share/food/c.json".to_owned(),
+        ];
+        assert_eq!(
+            owned_dirs("foo", &dests),
+            BTreeSet::from(
+                [
+                    "/etc/foo-daemon",
+                    "/etc/foo-daemon/conf.d",
+                    "/usr/share/foo",
+                    "/usr/share/foo/data",
+                ]
+                .map(str::to_owned)
+            )
+        );
+    }
+
+    #[test]
+    fn packaged_dirs_keep_their_mtime() {
+        let dir = TempDir::new("packaged-dirs");
+        let rpm = serde_json::json!({
+            "assets": [{ "content": "a = 1\n", "dest": "/etc/foo-daemon/conf.d/a.conf" }],
+        });
+        let entries = |rpms: Vec<PathBuf>| {
+            let rpm_pkg = rpm::Package::open(&rpms[0]).unwrap();
+            rpm_pkg.metadata.get_file_entries().unwrap()
+        };
+
+        let first = entries(build_assets(&dir.0, rpm.clone(), &[]));
+        let dirs: Vec<_> = first
+            .iter()
+            .filter(|e| matches!(e.mode, rpm::FileMode::Dir { .. }))
+            .map(|e| (e.path.to_str().unwrap(), e.mode.permissions()))
+            .collect();
+        assert_eq!(
+            dirs,
+            [
+                ("/etc/foo-daemon", 0o755),
+                ("/etc/foo-daemon/conf.d", 0o755)
+            ]
+        );
+
+        std::thread::sleep(Duration::from_millis(1100));
+        let second = entries(build_assets(&dir.0, rpm, &["--force"]));
+        let mtimes = |entries: &[rpm::FileEntry]| -> Vec<u32> {
+            entries.iter().map(|e| e.modified_at.0).collect()
+        };
+        assert_eq!(mtimes(&first), mtimes(&second));
+    }
+
+    #[test]
+    fn arch_of_standalone() {
+        let package = package("/work/a/Cargo.toml");
+        let triplet = Triplet::from_str("aarch64-unknown-linux-gnu").unwrap();
+        let standalone = ["cargo-make-rpm", "--standalone", "a", "--name", "a"];
+        let metadata = ["--set-version", "1.0", "--description", "A"];
+        let arch = |arch: &[&str]| {
+            let args = Cli::try_parse_from(standalone.iter().chain(&metadata).chain(arch))?;
+            Ok::<_, clap::Error>(package_arch(&args, &package, &triplet).unwrap())
+        };
+
+        assert_eq!(arch(&[]).unwrap(), "aarch64");
+        assert_eq!(arch(&["--arch", "noarch"]).unwrap(), "noarch");
+        assert_eq!(
+            arch(&["--arch", "x86_64", "--arch-variant", "v3"]).unwrap(),
+            "x86_64_v3"
+        );
+        assert!(arch(&["--arch", "amd64"]).is_err());
+        assert!(Cli::try_parse_from(["cargo-make-rpm", "--arch", "x86_64"]).is_err());
+    }
+
+    #[test]
+    fn compression_of_members() {
+        let member = |compression: &str| -> Package {
+            serde_json::from_value(serde_json::json!({
+                "id": "a 0.1.0",
+                "name": "a",
+                "version": "0.1.0",
+                "authors": [],
+                "targets": [],
+                "manifest_path": "/work/ws/a/Cargo.toml",
+                "metadata": { "rpm": { "compression": compression } },
+            }))
+            .unwrap()
+        };
+        let tool = member("gzip");
+        let data = member("xz");
+        let compression = |args: &[&str], package: &Package| {
+            let args = Cli::parse_from(["cargo-make-rpm"].iter().chain(args));
+            package_compression(&args, package)
+        };
+
+        // each member keeps its own unless the flag is given
+        assert_eq!(compression(&[], &tool), Compression::Gzip);
+        assert_eq!(compression(&[], &data), Compression::Xz);
+        let unset = package("/work/ws/b/Cargo.toml");
+        assert_eq!(compression(&[], &unset), Compression::Gzip);
+        let zstd = ["--compression", "zstd"];
+        assert_eq!(compression(&zstd, &tool), Compression::Zstd);
+        assert_eq!(compression(&zstd, &data), Compression::Zstd);
+    }
+
+    #[test]
+    fn blank_description() {
+        let mut package = package("/work/a/Cargo.toml");
+        for description in ["", "  ", " \n\t\r\n "] {
+            package.description = Some(description.to_owned());
+            assert_eq!(
+                package_summary(&package).unwrap_err(),
+                "Missing description in crate a"
+            );
+        }
+
+        package.description = Some("  A\n  package \n".to_owned());
+        assert_eq!(package_summary(&package).unwrap(), "A package");
+    }
+
+    #[test]
+    fn license_and_doc_flags() {
+        let dir = TempDir::new("license-flags");
+        let flags = |rpms: Vec<PathBuf>| -> Vec<(PathBuf, rpm::FileFlags)> {
+            let rpm_pkg = rpm::Package::open(&rpms[0]).unwrap();
+            let entries = rpm_pkg.metadata.get_file_entries().unwrap();
+            entries
+                .into_iter()
+                .filter(|e| !matches!(e.mode, rpm::FileMode::Dir { .. }))
+                .map(|e| (e.path, e.flags))
+                .collect()
+        };
+
+        fs::write(dir.0.join("LICENSE"), "MIT\n").unwrap();
+        fs::write(dir.0.join("README.md"), "# foo\n").unwrap();
+        let defaults = flags(build_assets(&dir.0, serde_json::json!({}), &["--defaults"]));
+        assert_eq!(
+            defaults,
+            [
+                ("/usr/share/doc/foo/README.md".into(), rpm::FileFlags::DOC),
+                (
+                    "/usr/share/licenses/foo/LICENSE".into(),
+                    rpm::FileFlags::LICENSE
+                ),
+            ]
+        );
+
+        let rpm = serde_json::json!({
+            "assets": [
+                { "content": "MIT\n", "dest": "/usr/share/licenses/foo/NOTICE" },
+                { "content": "MIT\n", "dest": "/usr/share/foo/COPYING", "license": true },
+            ],
+        });
+        let marked = flags(build_assets(&dir.0, rpm, &["--force"]));
+        assert_eq!(
+            marked,
+            [
+                ("/usr/share/foo/COPYING".into(), rpm::FileFlags::LICENSE),
+                (
+                    "/usr/share/licenses/foo/NOTICE".into(),
+                    rpm::FileFlags::LICENSE
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn uncompressed_payload() {
+        let dir = TempDir::new("uncompressed");
+        let rpm = serde_json::json!({
+            "assets": [{ "content": "hello\n", "dest": "/usr/share/foo/hello" }],
+        });
+        let rpms = build_assets(&dir.0, rpm, &["--compression", "none"]);
+
+        let rpm_pkg = rpm::Package::open(&rpms[0]).unwrap();
+        rpm_pkg.verify_digests().unwrap();
+        assert_eq!(
+            rpm_pkg.metadata.get_payload_compressor().unwrap(),
+            rpm::CompressionType::None
+        );
+        // a plain newc cpio archive, with the file's contents as they are
+        assert!(rpm_pkg.content.starts_with(b"070701"));
+        let contents = rpm_pkg.content.windows(6).any(|w| w == b"hello\n");
+        assert!(contents);
+    }
+
+    #[test]
+    fn target_dependencies_of_envs() {
+        let dir = TempDir::new("target-dependencies");
+        let rpm = serde_json::json!({
+            "dependencies": ["ca-certificates"],
+            "target": {
+                "cfg(target_env = \"musl\")": { "dependencies": ["musl-libc"] },
+            },
+        });
+        let requires = |triple: &str| -> Vec<String> {
+            let rpms = try_build(&dir.0, rpm.clone(), &["foo"], triple, &[]).unwrap();
+            let rpm_pkg = rpm::Package::open(&rpms[0]).unwrap();
+            let requires = rpm_pkg.metadata.get_requires().unwrap();
+            // rpm's own rpmlib() requirements are left out
+            requires
+                .into_iter()
+                .map(|d| d.name)
+                .filter(|name| !name.starts_with("rpmlib("))
+                .collect()
+        };
+
+        assert_eq!(requires("x86_64-unknown-linux-gnu"), ["ca-certificates"]);
+        assert_eq!(
+            requires("x86_64-unknown-linux-musl"),
+            ["ca-certificates", "musl-libc"]
+        );
+    }
+
+    #[test]
+    fn bin_paths() {
+        let dir = TempDir::new("bin-paths");
+        let rpm = serde_json::json!({
+            "bins": {
+                "mydaemon": { "path": "/usr/libexec/foo/mydaemon" },
+                "helper": { "bindir": false },
+            },
+        });
+        let rpms = try_build(
+            &dir.0,
+            rpm,
+            &["foo", "mydaemon", "helper"],
+            "x86_64-unknown-linux-gnu",
+            &["--no-default-bindir"],
+        )
+        .unwrap();
+
+        let rpm_pkg = rpm::Package::open(&rpms[0]).unwrap();
+        let files: Vec<_> = rpm_pkg
+            .metadata
+            .get_file_entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.path.to_string_lossy().into_owned(), e.mode.permissions()))
+            .collect();
+        // the package owns the directory named after it, the other binaries aren't installed
+        assert_eq!(
+            files,
+            [
+                ("/usr/libexec/foo".to_owned(), 0o755),
+                ("/usr/libexec/foo/mydaemon".to_owned(), 0o755)
+            ]
+        );
+
+        let mut package = package("/work/a/Cargo.toml");
+        package.targets = vec![Target {
+            name: "a".to_owned(),
+            kind: vec!["bin".to_owned()],
+        }];
+        let mut bins = |bins: serde_json::Value| {
+            package.metadata =
+                serde_json::from_value(serde_json::json!({ "rpm": { "bins": bins } })).unwrap();
+            package.check_install_names()
+        };
+        assert!(bins(serde_json::json!({ "a": { "path": "/usr/libexec/a" } })).is_ok());
+        assert!(bins(serde_json::json!({ "a": { "path": "libexec/a" } })).is_err());
+        assert!(bins(serde_json::json!({ "a": { "path": "/usr/libexec/" } })).is_err());
+        let both = serde_json::json!({ "a": { "path": "/usr/libexec/a", "install_name": "b" } });
+        assert!(bins(both).is_err());
+    }
+
+    #[test]
+    fn expected_files_of_parts() {
+        let dir = TempDir::new("expected-parts");
+        let rpm = serde_json::json!({
+            "assets": [
+                { "content": "aaaa", "dest": "/usr/share/foo/a" },
+                { "content": "bbbb", "dest": "/usr/share/foo/b" },
+            ],
+        });
+        let expect_files = dir.0.join("expected");
+        let expect = expect_files.to_str().unwrap();
+
+        fs::write(&expect_files, "/usr/share/foo/a\n/usr/share/foo/b\n").unwrap();
+        let rpms = build_assets(
+            &dir.0,
+            rpm.clone(),
+            &["--max-size", "6", "--expect-files", expect],
+        );
+        let names: Vec<_> = rpms.iter().map(|p| p.file_name().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "foo-0.1.0.x86_64.rpm",
+                "foo-part1-0.1.0.x86_64.rpm",
+                "foo-part2-0.1.0.x86_64.rpm"
+            ]
+        );
+
+        fs::write(&expect_files, "/usr/share/foo/a\n").unwrap();
+        let err = try_build_assets(&dir.0, rpm, &["--max-size", "6", "--expect-files", expect]);
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("unexpected file /usr/share/foo/b"));
+    }
+
+    #[test]
+    fn arches_of_hosts() {
+        assert!(runs_on("arm-nofp", "armhfp"));
+        assert!(runs_on("armv7hl", "armhfp"));
+        assert!(runs_on("noarch", "aarch64"));
+        assert!(runs_on("i686", "i386"));
+        assert!(!runs_on("aarch64", "armhfp"));
+        assert!(!runs_on("i686", "x86_64"));
+    }
+
+    #[test]
+    fn existing_parts() {
+        let dir = TempDir::new("existing-parts");
+        let rpm = serde_json::json!({
+            "assets": [
+                { "content": "aaaa", "dest": "/usr/share/foo/a" },
+                { "content": "bbbb", "dest": "/usr/share/foo/b" },
+            ],
+        });
+        let args = ["--max-size", "6"];
+        let exists = || {
+            package_exists(
+                &foo_cli(&dir.0, &args),
+                &Manifest::default(),
+                &Triplet::from_str("x86_64-unknown-linux-gnu").unwrap(),
+                None,
+                &foo(&dir.0, rpm.clone(), &[]),
+            )
+            .unwrap()
+        };
+
+        assert!(!exists());
+        let rpms = build_assets(&dir.0, rpm.clone(), &args);
+        assert!(exists());
+        fs::remove_file(&rpms[2]).unwrap();
+        assert!(!exists());
+    }
+
+    #[test]
+    fn input_hash_of_output_options() {
+        let package = package("/work/a/Cargo.toml");
+        let triplet = Triplet::from_str("x86_64-unknown-linux-gnu").unwrap();
+        let hash = |args: &[&str]| {
+            let args = Cli::parse_from(["cargo-make-rpm"].iter().chain(args));
+            InputHash::new(&args, &triplet, &package).finish()
+        };
+
+        let plain = hash(&[]);
+        assert_eq!(plain.len(), 64);
+        assert_eq!(
+            hash(&[
+                "--timings",
+                "--verbose",
+                "--keep-going",
+                "--report-json",
+                "r.json"
+            ]),
+            plain
+        );
+        assert_ne!(hash(&["--set-release", "2"]), plain);
+    }
+
+    #[test]
+    fn unknown_keys_of_asset_tables() {
+        let err = serde_json::from_value::<Asset>(serde_json::json!({
+            "source": "a.conf",
+            "dest": "/etc/a.conf",
+            "mdoe": "644",
+        }))
+        .unwrap_err();
+        assert_eq!(err.to_string(), "unknown key `mdoe`, did you mean `mode`?");
+
+        let err = serde_json::from_value::<OwnedDir>(serde_json::json!({
+            "path": "/var/lib/a",
+            "grup": "a",
+        }))
+        .unwrap_err();
+        assert_eq!(err.to_string(), "unknown key `grup`, did you mean `group`?");
+
+        let short = serde_json::json!(["a.conf", "/etc/a.conf", "644"]);
+        assert!(matches!(
+            serde_json::from_value(short).unwrap(),
+            Asset::Short(..)
+        ));
+        assert!(matches!(
+            serde_json::from_value(serde_json::json!("/var/lib/a")).unwrap(),
+            OwnedDir::Path(_)
+        ));
+    }
+}
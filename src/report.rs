@@ -0,0 +1,75 @@
+use std::{
+    error::Error,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// A machine readable summary of the rpms written by a run, for `--report-json`
+#[derive(Serialize, Debug)]
+pub struct Report {
+    packages: Vec<PackageReport>,
+}
+
+#[derive(Serialize, Debug)]
+struct PackageReport {
+    path: PathBuf,
+    name: String,
+    version: String,
+    release: String,
+    arch: String,
+    size: u64,
+    sha256: String,
+    signed: bool,
+    files: Vec<PathBuf>,
+}
+
+impl Report {
+    /// Read the written rpms back, so the report describes exactly what is on disk
+    pub fn new(rpms: &[PathBuf]) -> Result<Report, Box<dyn Error>> {
+        let mut packages = Vec::with_capacity(rpms.len());
+        for path in rpms {
+            let data = fs::read(path)?;
+            let package = rpm::Package::parse(&mut data.as_slice())?;
+            let metadata = &package.metadata;
+
+            let mut sha256 = String::with_capacity(64);
+            for byte in Sha256::digest(&data) {
+                write!(sha256, "{byte:02x}")?;
+            }
+
+            packages.push(PackageReport {
+                path: path.canonicalize()?,
+                name: metadata.get_name()?.to_owned(),
+                version: metadata.get_version()?.to_owned(),
+                release: metadata.get_release()?.to_owned(),
+                arch: metadata.get_arch()?.to_owned(),
+                size: data.len() as u64,
+                sha256,
+                signed: package.signature_key_ids().is_ok_and(|ids| !ids.is_empty()),
+                files: metadata.get_file_paths().unwrap_or_default(),
+            });
+        }
+
+        Ok(Report { packages })
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// The path and sha256 of each rpm
+    pub fn checksums(&self) -> impl Iterator<Item = (&Path, &str)> {
+        self.packages
+            .iter()
+            .map(|p| (p.path.as_path(), p.sha256.as_str()))
+    }
+}
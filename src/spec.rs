@@ -0,0 +1,481 @@
+use std::{
+    collections::BTreeMap, error::Error, fmt::Write, fs, os::unix::fs::PermissionsExt, path::Path,
+};
+
+use crate::{
+    asset_files, buildroot_files, crate_dir, default_assets, git_head, owned_dirs, package_arch,
+    package_description, package_license, package_release, package_summary, package_version,
+    parse_mode, parse_owner, parse_verify, target_dir, vendor, Asset, AssetTable, Cli,
+    DefaultAsset, Manifest, OwnedDir, OwnedDirTable, Package, ScriptletKind, TargetKind, Triplet,
+};
+
+/// A file of the `%files` section, with the directives it's listed with
+struct SpecFile {
+    path: String,
+    directives: Vec<String>,
+}
+
+/// A `.spec` file that builds the same rpm as [`crate::build_package`] with `rpmbuild`, for
+/// `--print-spec`. Paths in `%build` and `%install` are relative to the directory assets are
+/// resolved from
+pub fn spec(
+    args: &Cli,
+    manifest: &Manifest,
+    triplet: &Triplet,
+    target: Option<&str>,
+    package: &Package,
+) -> Result<String, Box<dyn Error>> {
+    let crate_dir = crate_dir(manifest.workspace_root.as_ref(), package);
+    let options = package.rpm_options();
+    if args.max_size.is_some() {
+        return Err("--print-spec can't split the assets into parts, leave out --max-size".into());
+    }
+
+    if options.is_some_and(|r| r.features.as_ref().is_some_and(|f| !f.is_empty())) {
+        return Err(format!(
+            "{}: --print-spec can't write the subpackages of features",
+            package.name
+        )
+        .into());
+    }
+
+    let arch = package_arch(args, package, triplet)?;
+    let release = package_release(args, package, target)?;
+    let release = release.as_deref().unwrap_or("1");
+
+    let mut spec = String::new();
+    writeln!(spec, "%global crate_dir {}", crate_dir.display())?;
+    writeln!(spec)?;
+    writeln!(spec, "Name:           {}", package.name)?;
+    writeln!(spec, "Version:        {}", package_version(args, package))?;
+    writeln!(spec, "Release:        {release}")?;
+    writeln!(
+        spec,
+        "Summary:        {}",
+        escape(&package_summary(package)?)
+    )?;
+    writeln!(spec, "License:        {}", package_license(args, package)?)?;
+    if let Some(url) = package.url() {
+        writeln!(spec, "URL:            {url}")?;
+    }
+
+    if let Some(ref repository) = package.repository {
+        let commit = if args.vcs_commit {
+            git_head(&crate_dir)
+        } else {
+            None
+        };
+        match commit {
+            Some(commit) => writeln!(spec, "VCS:            git:{repository}#{commit}")?,
+            None => writeln!(spec, "VCS:            git:{repository}")?,
+        }
+    }
+
+    let vendor = args
+        .vendor
+        .clone()
+        .unwrap_or_else(|| vendor(&package.authors));
+    if !vendor.is_empty() {
+        writeln!(spec, "Vendor:         {vendor}")?;
+    }
+
+    if arch == "noarch" {
+        writeln!(spec, "BuildArch:      noarch")?;
+    }
+
+    if let Some(options) = options {
+        if let Some(ref arches) = options.exclusive_arch {
+            writeln!(spec, "ExclusiveArch:  {}", arches.join(" "))?;
+        }
+
+        if let Some(ref arches) = options.exclude_arch {
+            writeln!(spec, "ExcludeArch:    {}", arches.join(" "))?;
+        }
+
+        let target_options = options.target_options(triplet)?;
+        let dependencies = options
+            .dependencies
+            .iter()
+            .chain(
+                target_options
+                    .iter()
+                    .filter_map(|t| t.dependencies.as_ref()),
+            )
+            .flatten();
+        for dep in dependencies {
+            writeln!(spec, "Requires:       {dep}")?;
+        }
+
+        for conflict in options.conflicts.iter().flatten() {
+            writeln!(spec, "Conflicts:      {conflict}")?;
+        }
+    }
+
+    let description = match package_description(package, &crate_dir)? {
+        Some(description) => description,
+        None => package_summary(package)?,
+    };
+    writeln!(spec, "\n%description\n{}", escape(&description))?;
+
+    // members outside the workspace root are still built in the workspace's target directory
+    let target_dir = target_dir(manifest, &crate_dir);
+    let release_dir = match target {
+        Some(target) => format!("{}/{target}/release", target_dir.display()),
+        None => format!("{}/release", target_dir.display()),
+    };
+
+    let mut build = format!("cargo build --release -p {}", package.name);
+    if let Some(target) = target {
+        write!(build, " --target {target}")?;
+    }
+
+    for arg in package.build_args() {
+        write!(build, " {arg}")?;
+    }
+
+    let mut install = Vec::new();
+    let mut files = Vec::new();
+    let mut dests = Vec::new();
+    for (target, kind) in package.packaged_targets() {
+        match kind {
+            TargetKind::Bin => {
+                let Some(dest) = package.bin_dest(&target.name, !args.no_default_bindir) else {
+                    continue;
+                };
+                let is_ghost = options
+                    .and_then(|r| r.ghost_bins.as_ref())
+                    .is_some_and(|g| g.contains(&target.name));
+                if is_ghost {
+                    files.push(SpecFile::new(dest).directive("%ghost"));
+                    continue;
+                }
+
+                install.push(format!(
+                    "install -Dm755 {release_dir}/{} %{{buildroot}}{dest}",
+                    target.name
+                ));
+                dests.push(dest.clone());
+                files.push(SpecFile::new(dest));
+            }
+            TargetKind::Cdylib | TargetKind::Dylib | TargetKind::Staticlib => {
+                let (extension, mode) = match kind {
+                    TargetKind::Staticlib => ("a", "644"),
+                    _ => ("so", "755"),
+                };
+                let filename = format!("lib{}.{extension}", target.name.replace('-', "_"));
+                let dest = format!("{}/{filename}", triplet.libdir());
+                install.push(format!(
+                    "install -Dm{mode} {release_dir}/{filename} %{{buildroot}}{dest}"
+                ));
+                files.push(SpecFile::new(dest));
+            }
+        }
+    }
+
+    let examples_dir = options
+        .and_then(|r| r.examples_dir.as_deref())
+        .unwrap_or("/usr/bin");
+    let examples_prefix = options
+        .and_then(|r| r.examples_prefix.clone())
+        .unwrap_or_else(|| format!("{}-", package.name));
+    for example in package.examples() {
+        let dest = format!(
+            "{}/{examples_prefix}{example}",
+            examples_dir.trim_end_matches('/')
+        );
+        install.push(format!(
+            "install -Dm755 {release_dir}/examples/{example} %{{buildroot}}{dest}"
+        ));
+        dests.push(dest.clone());
+        files.push(SpecFile::new(dest));
+    }
+
+    let assets = options.and_then(|r| r.assets.as_ref());
+    for asset in assets.into_iter().flatten() {
+        let (dest, mode) = match asset {
+            Asset::Short(_, dest, mode) => (dest, Some(mode)),
+            Asset::Table(AssetTable { dest, mode, .. }) => (dest, mode.as_ref()),
+        };
+
+        let source = match asset {
+            Asset::Short(source, ..)
+            | Asset::Table(AssetTable {
+                source: Some(source),
+                content: None,
+                ..
+            }) => source,
+            Asset::Table(AssetTable {
+                source: None,
+                content: Some(content),
+                ..
+            }) if !dest.ends_with('/') => {
+                let mode = mode.map_or(Ok(0o644), |m| parse_mode(m, dest))?;
+                install.push(format!("mkdir -p %{{buildroot}}{}", parent(dest)));
+                install.push(format!(
+                    "cat > %{{buildroot}}{dest} <<'EOF'\n{}\nEOF",
+                    escape(content.strip_suffix('\n').unwrap_or(content))
+                ));
+                install.push(format!("chmod {mode:o} %{{buildroot}}{dest}"));
+                dests.push(dest.clone());
+                files.push(asset_file(asset, dest.clone())?);
+                continue;
+            }
+            Asset::Table(AssetTable { content: None, .. })
+            | Asset::Table(AssetTable {
+                source: Some(_), ..
+            }) => {
+                return Err(format!("asset {dest} needs exactly one of source or content").into())
+            }
+            Asset::Table(_) => {
+                return Err(
+                    format!("asset {dest} has inline content, its dest needs a file name").into(),
+                )
+            }
+        };
+
+        // the build script's OUT_DIR is only known once it ran, so the newest one is used
+        let sources = match source.strip_prefix("$OUT_DIR/") {
+            Some(file) => {
+                let out_dir = format!(
+                    "$(ls -td {release_dir}/build/{}-*/out | head -n1)",
+                    package.name
+                );
+                let dest = match dest.strip_suffix('/') {
+                    Some(dir) => format!("{dir}/{}", file.rsplit('/').next().unwrap_or(file)),
+                    None => dest.clone(),
+                };
+                vec![(format!("\"{out_dir}/{file}\""), dest)]
+            }
+            None => asset_files(source, dest, &crate_dir, None)?
+                .into_iter()
+                .map(|(path, dest)| {
+                    let path = path.strip_prefix(&crate_dir).unwrap_or(&path);
+                    (path.to_string_lossy().into_owned(), dest)
+                })
+                .collect(),
+        };
+
+        for (source, mut dest) in sources {
+            let mode = match mode {
+                Some(mode) => format!("{:o}", parse_mode(mode, &dest)?),
+                None if crate_dir.join(&source).is_dir() => "755".to_owned(),
+                None => "644".to_owned(),
+            };
+
+            if let Asset::Table(AssetTable { gzip: true, .. }) = asset {
+                if !dest.ends_with(".gz") {
+                    dest.push_str(".gz");
+                }
+                install.push(format!("mkdir -p %{{buildroot}}{}", parent(&dest)));
+                install.push(format!("gzip -9nc {source} > %{{buildroot}}{dest}"));
+                install.push(format!("chmod {mode} %{{buildroot}}{dest}"));
+            } else {
+                install.push(format!("install -Dm{mode} {source} %{{buildroot}}{dest}"));
+            }
+
+            dests.push(dest.clone());
+            files.push(asset_file(asset, dest)?);
+        }
+    }
+
+    // copied with the mode each file has in the buildroot and owned by root, like when building.
+    // %install runs in the crate directory, so the buildroot is given as an absolute path
+    let staged = match args.buildroot {
+        Some(ref buildroot) => buildroot_files(&buildroot.canonicalize()?)?,
+        None => Vec::new(),
+    };
+    for (path, dest) in staged {
+        let metadata = path.symlink_metadata()?;
+        let mode = metadata.permissions().mode() & 0o7777;
+        dests.push(dest.clone());
+
+        if metadata.is_symlink() {
+            let target = fs::read_link(&path)?;
+            install.push(format!("mkdir -p %{{buildroot}}{}", parent(&dest)));
+            install.push(format!("ln -s {} %{{buildroot}}{dest}", target.display()));
+            files.push(SpecFile::new(dest));
+        } else if metadata.is_dir() {
+            install.push(format!("mkdir -p %{{buildroot}}{dest}"));
+            let attr = format!("%attr({mode:o},root,root)");
+            files.push(SpecFile::new(dest).directive("%dir").directive(attr));
+        } else {
+            install.push(format!(
+                "install -Dm{mode:o} {} %{{buildroot}}{dest}",
+                path.display()
+            ));
+            files.push(SpecFile::new(dest).directive(format!("%attr({mode:o},root,root)")));
+        }
+    }
+
+    let has_assets = assets.is_some_and(|a| !a.is_empty());
+    if args.defaults && !has_assets && !package.is_meta() {
+        let package_dir = Path::new(&package.manifest_path)
+            .parent()
+            .ok_or("invalid manifest path")?;
+
+        for (path, kind) in default_assets(package_dir, &crate_dir)? {
+            let name = path
+                .file_name()
+                .ok_or("invalid asset path")?
+                .to_string_lossy();
+            let (dest, directive) = match kind {
+                DefaultAsset::License => (
+                    format!("/usr/share/licenses/{}/{name}", package.name),
+                    "%license",
+                ),
+                DefaultAsset::Readme => (format!("/usr/share/doc/{}/{name}", package.name), "%doc"),
+            };
+
+            let source = path.strip_prefix(&crate_dir).unwrap_or(&path);
+            install.push(format!(
+                "install -Dm644 {} %{{buildroot}}{dest}",
+                source.display()
+            ));
+            dests.push(dest.clone());
+            files.push(SpecFile::new(dest).directive(directive));
+        }
+    }
+
+    let symlinks: Option<&BTreeMap<String, String>> = options.and_then(|r| r.symlinks.as_ref());
+    for (link, target) in symlinks.into_iter().flatten() {
+        install.push(format!("mkdir -p %{{buildroot}}{}", parent(link)));
+        install.push(format!("ln -s {target} %{{buildroot}}{link}"));
+        dests.push(link.clone());
+        files.push(SpecFile::new(link.clone()));
+    }
+
+    let configured_dirs = options.and_then(|r| r.owned_dirs.as_ref());
+    for dir in configured_dirs.into_iter().flatten() {
+        let path = dir.path().trim_end_matches('/').to_owned();
+        // checked the same way as when building
+        dir.file_options()?;
+        install.push(format!("mkdir -p %{{buildroot}}{path}"));
+        dests.push(path.clone());
+
+        let mut file = SpecFile::new(path).directive("%dir");
+        if let OwnedDir::Table(OwnedDirTable { user, group, .. }) = dir {
+            file = file.directive(format!(
+                "%attr({:o},{},{})",
+                dir.mode()?,
+                user.as_deref().unwrap_or("-"),
+                group.as_deref().unwrap_or("-")
+            ));
+        }
+        files.push(file);
+    }
+
+    for dir in owned_dirs(&package.name, &dests) {
+        files.push(SpecFile::new(dir).directive("%dir"));
+    }
+
+    writeln!(spec, "\n%prep\n\n%build\ncd %{{crate_dir}}")?;
+    if !package.assets_only() {
+        writeln!(spec, "{build}")?;
+    }
+
+    writeln!(spec, "\n%install\ncd %{{crate_dir}}")?;
+    for line in install {
+        writeln!(spec, "{line}")?;
+    }
+
+    if let Some(options) = options {
+        let bins: Vec<&str> = package
+            .packaged_targets()
+            .into_iter()
+            .filter(|(_, kind)| *kind == TargetKind::Bin)
+            .map(|(target, _)| target.name.as_str())
+            .collect();
+        for (kind, section) in [
+            (ScriptletKind::PreInstall, "%pre"),
+            (ScriptletKind::PostInstall, "%post"),
+            (ScriptletKind::PreUninstall, "%preun"),
+            (ScriptletKind::PostUninstall, "%postun"),
+        ] {
+            let Some(scriptlet) = options.scriptlet(kind, triplet, &bins)? else {
+                continue;
+            };
+
+            match scriptlet.program {
+                Some(program) => writeln!(spec, "\n{section} -p {}", program.join(" "))?,
+                None => writeln!(spec, "\n{section}")?,
+            }
+            writeln!(spec, "{}", escape(scriptlet.script.trim_end()))?;
+        }
+    }
+
+    writeln!(spec, "\n%files")?;
+    for file in files {
+        for directive in file.directives {
+            write!(spec, "{directive} ")?;
+        }
+        writeln!(spec, "{}", file.path)?;
+    }
+
+    Ok(spec)
+}
+
+impl SpecFile {
+    fn new(path: String) -> SpecFile {
+        SpecFile {
+            path,
+            directives: Vec::new(),
+        }
+    }
+
+    fn directive(mut self, directive: impl Into<String>) -> SpecFile {
+        self.directives.push(directive.into());
+        self
+    }
+}
+
+/// The `%files` entry of an asset installed at `dest`
+fn asset_file(asset: &Asset, dest: String) -> Result<SpecFile, String> {
+    let mut file = SpecFile::new(dest);
+    match asset.marks(&file.path)? {
+        (true, _) => file = file.directive("%doc"),
+        (_, true) => file = file.directive("%license"),
+        _ => {}
+    }
+
+    if let Asset::Table(AssetTable {
+        verify,
+        user,
+        group,
+        caps,
+        ..
+    }) = asset
+    {
+        if let Some(verify) = verify {
+            parse_verify(verify, &file.path)?;
+            file = file.directive(format!("%verify({verify})"));
+        }
+
+        if user.is_some() || group.is_some() {
+            let user = match user {
+                Some(user) => parse_owner(user, "user", &file.path)?,
+                None => "-",
+            };
+            let group = match group {
+                Some(group) => parse_owner(group, "group", &file.path)?,
+                None => "-",
+            };
+            file = file.directive(format!("%attr(-,{user},{group})"));
+        }
+
+        if let Some(caps) = caps {
+            file = file.directive(format!("%caps({caps})"));
+        }
+    }
+
+    Ok(file)
+}
+
+/// The directory a path is in
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("/", |(dir, _)| dir)
+}
+
+/// Escape the `%` of text that rpmbuild would otherwise expand as macros
+fn escape(text: &str) -> String {
+    text.replace('%', "%%")
+}
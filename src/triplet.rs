@@ -0,0 +1,376 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// The arches rpm knows
+pub const RPM_ARCHES: &[&str] = &[
+    "aarch64",
+    "alpha",
+    "armhfp",
+    "armv4tl",
+    "armv5tel",
+    "armv6hl",
+    "armv6l",
+    "armv7hl",
+    "armv7l",
+    "i386",
+    "i486",
+    "i586",
+    "i686",
+    "ia64",
+    "loongarch64",
+    "m68k",
+    "mips",
+    "mips64",
+    "mips64el",
+    "mips64r6",
+    "mips64r6el",
+    "mipsel",
+    "mipsr6",
+    "mipsr6el",
+    "noarch",
+    "ppc",
+    "ppc64",
+    "ppc64le",
+    "riscv32",
+    "riscv64",
+    "s390",
+    "s390x",
+    "sparc",
+    "sparc64",
+    "sparcv9",
+    "x86_64",
+    "x86_64_v2",
+    "x86_64_v3",
+    "x86_64_v4",
+];
+
+/// A target triple split into its parts
+#[derive(Debug)]
+pub struct Triplet {
+    pub arch: String,
+    pub vendor: String,
+    pub os: String,
+    pub libc: Option<String>,
+    /// The triple as it was written, android triples leave out the vendor
+    triple: String,
+}
+
+impl Triplet {
+    /// The rpm arch of packages built for the target
+    pub fn rpm_arch(&self) -> String {
+        match self.arch.as_str() {
+            // the float ABI is only in the last part, e.g. `gnueabi` or `gnueabihf`
+            "armv7" | "arm" | "thumbv7neon" => {
+                if self.libc.as_ref().is_none_or(|libc| libc.ends_with("hf")) {
+                    "armhfp"
+                } else {
+                    "arm-nofp"
+                }
+            }
+            "armv5te" => "armv5tel",
+            "armv4t" => "armv4tl",
+            "powerpc" => "ppc",
+            "powerpc64" => "ppc64",
+            "powerpc64le" => "ppc64le",
+            "riscv64gc" | "riscv64a23" => "riscv64",
+            "riscv32gc" => "riscv32",
+            "mipsisa32r6" => "mipsr6",
+            "mipsisa32r6el" => "mipsr6el",
+            "mipsisa64r6" => "mips64r6",
+            "mipsisa64r6el" => "mips64r6el",
+            arch => arch,
+        }
+        .to_owned()
+    }
+
+    /// The architecture as rustc names it in `cfg(target_arch)`
+    pub fn cfg_arch(&self) -> &str {
+        match self.arch.as_str() {
+            "i386" | "i586" | "i686" => "x86",
+            arch if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+            "aarch64_be" => "aarch64",
+            "riscv64gc" | "riscv64a23" => "riscv64",
+            "riscv32gc" | "riscv32imac" => "riscv32",
+            "powerpc64le" => "powerpc64",
+            "mips64el" => "mips64",
+            "mipsel" => "mips",
+            "mipsisa32r6" | "mipsisa32r6el" => "mips32r6",
+            "mipsisa64r6" | "mipsisa64r6el" => "mips64r6",
+            "sparcv9" => "sparc64",
+            arch => arch,
+        }
+    }
+
+    /// The libc as rustc names it in `cfg(target_env)`, without the abi suffix
+    pub fn env(&self) -> &str {
+        let libc = self.libc.as_deref().unwrap_or_default();
+        ["gnu", "musl", "uclibc", "ohos"]
+            .into_iter()
+            .find(|env| libc.starts_with(env))
+            .unwrap_or_default()
+    }
+
+    /// The families as rustc names them in `cfg(target_family)`, which are also bare names like
+    /// `cfg(unix)`
+    pub fn families(&self) -> Vec<&str> {
+        let mut families = Vec::new();
+        if self.arch.starts_with("wasm") {
+            families.push("wasm");
+        }
+        match self.os.as_str() {
+            "windows" => families.push("windows"),
+            "none" | "unknown" | "uefi" | "wasi" | "wasip1" | "wasip2" => {}
+            _ => families.push("unix"),
+        }
+        families
+    }
+
+    /// The pointer width in bits as rustc names it in `cfg(target_pointer_width)`
+    pub fn pointer_width(&self) -> &str {
+        let libc = self.libc.as_deref().unwrap_or_default();
+        if libc.ends_with("x32") || libc.ends_with("ilp32") || libc.ends_with("abin32") {
+            return "32";
+        }
+
+        match self.cfg_arch() {
+            "x86_64" | "aarch64" | "powerpc64" | "s390x" | "sparc64" | "mips64" | "mips64r6"
+            | "riscv64" | "loongarch64" | "wasm64" | "bpf" => "64",
+            "avr" | "msp430" => "16",
+            _ => "32",
+        }
+    }
+
+    /// The byte order as rustc names it in `cfg(target_endian)`
+    pub fn endian(&self) -> &str {
+        match self.arch.as_str() {
+            "aarch64_be" | "powerpc" | "powerpc64" | "s390x" | "sparc" | "sparc64" | "sparcv9"
+            | "m68k" | "mips" | "mips64" | "mipsisa32r6" | "mipsisa64r6" | "bpfeb" => "big",
+            arch if arch.starts_with("armeb") || arch.starts_with("thumbeb") => "big",
+            _ => "little",
+        }
+    }
+
+    /// The directory libraries are installed to
+    pub fn libdir(&self) -> &'static str {
+        // the x32 ABI has its own libdir next to the 64-bit one
+        if self
+            .libc
+            .as_deref()
+            .is_some_and(|libc| libc.ends_with("x32"))
+        {
+            return "/usr/libx32";
+        }
+
+        match self.cfg_arch() {
+            "x86_64" | "aarch64" | "powerpc64" | "s390x" | "sparc64" | "mips64" | "mips64r6"
+            | "riscv64" | "loongarch64" => "/usr/lib64",
+            _ => "/usr/lib",
+        }
+    }
+}
+
+impl Display for Triplet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.triple)
+    }
+}
+
+impl FromStr for Triplet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('-');
+        let arch = parts.next().ok_or("Invalid target triplet: No arch")?;
+        let vendor = parts.next().ok_or("Invalid target triplet: No vendor")?;
+        let os = parts.next().ok_or("Invalid target triplet: No os")?;
+        let libc = parts.next();
+
+        // `<arch>-linux-android` has no vendor, and rustc calls its os android
+        let (vendor, os, libc) = match (vendor, libc) {
+            ("linux", None) if os.starts_with("android") => ("unknown", "android", Some(os)),
+            _ => (vendor, os, libc),
+        };
+
+        Ok(Triplet {
+            arch: arch.to_owned(),
+            vendor: vendor.to_owned(),
+            os: os.to_owned(),
+            libc: libc.map(|s| s.to_owned()),
+            triple: s.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Linux triples rustc supports with the rpm arch, `target_arch`, `target_env` and
+    /// libdir of each
+    #[rustfmt::skip]
+    const TRIPLES: &[(&str, &str, &str, &str, &str)] = &[
+        ("aarch64-unknown-linux-gnu", "aarch64", "aarch64", "gnu", "/usr/lib64"),
+        ("aarch64-unknown-linux-gnu_ilp32", "aarch64", "aarch64", "gnu", "/usr/lib64"),
+        ("aarch64-unknown-linux-musl", "aarch64", "aarch64", "musl", "/usr/lib64"),
+        ("aarch64-unknown-linux-ohos", "aarch64", "aarch64", "ohos", "/usr/lib64"),
+        ("aarch64_be-unknown-linux-gnu", "aarch64_be", "aarch64", "gnu", "/usr/lib64"),
+        ("aarch64_be-unknown-linux-musl", "aarch64_be", "aarch64", "musl", "/usr/lib64"),
+        ("arm-unknown-linux-gnueabi", "arm-nofp", "arm", "gnu", "/usr/lib"),
+        ("arm-unknown-linux-gnueabihf", "armhfp", "arm", "gnu", "/usr/lib"),
+        ("arm-unknown-linux-musleabi", "arm-nofp", "arm", "musl", "/usr/lib"),
+        ("arm-unknown-linux-musleabihf", "armhfp", "arm", "musl", "/usr/lib"),
+        ("armeb-unknown-linux-gnueabi", "armeb", "arm", "gnu", "/usr/lib"),
+        ("armv4t-unknown-linux-gnueabi", "armv4tl", "arm", "gnu", "/usr/lib"),
+        ("armv5te-unknown-linux-gnueabi", "armv5tel", "arm", "gnu", "/usr/lib"),
+        ("armv5te-unknown-linux-musleabi", "armv5tel", "arm", "musl", "/usr/lib"),
+        ("armv5te-unknown-linux-uclibceabi", "armv5tel", "arm", "uclibc", "/usr/lib"),
+        ("armv7-unknown-linux-gnueabi", "arm-nofp", "arm", "gnu", "/usr/lib"),
+        ("armv7-unknown-linux-gnueabihf", "armhfp", "arm", "gnu", "/usr/lib"),
+        ("armv7-unknown-linux-musleabi", "arm-nofp", "arm", "musl", "/usr/lib"),
+        ("armv7-unknown-linux-musleabihf", "armhfp", "arm", "musl", "/usr/lib"),
+        ("armv7-unknown-linux-ohos", "arm-nofp", "arm", "ohos", "/usr/lib"),
+        ("armv7-unknown-linux-uclibceabi", "arm-nofp", "arm", "uclibc", "/usr/lib"),
+        ("armv7-unknown-linux-uclibceabihf", "armhfp", "arm", "uclibc", "/usr/lib"),
+        ("csky-unknown-linux-gnuabiv2", "csky", "csky", "gnu", "/usr/lib"),
+        ("hexagon-unknown-linux-musl", "hexagon", "hexagon", "musl", "/usr/lib"),
+        ("i586-unknown-linux-gnu", "i586", "x86", "gnu", "/usr/lib"),
+        ("i586-unknown-linux-musl", "i586", "x86", "musl", "/usr/lib"),
+        ("i686-unknown-linux-gnu", "i686", "x86", "gnu", "/usr/lib"),
+        ("i686-unknown-linux-musl", "i686", "x86", "musl", "/usr/lib"),
+        ("loongarch64-unknown-linux-gnu", "loongarch64", "loongarch64", "gnu", "/usr/lib64"),
+        ("loongarch64-unknown-linux-musl", "loongarch64", "loongarch64", "musl", "/usr/lib64"),
+        ("m68k-unknown-linux-gnu", "m68k", "m68k", "gnu", "/usr/lib"),
+        ("mips-unknown-linux-gnu", "mips", "mips", "gnu", "/usr/lib"),
+        ("mips-unknown-linux-musl", "mips", "mips", "musl", "/usr/lib"),
+        ("mips-unknown-linux-uclibc", "mips", "mips", "uclibc", "/usr/lib"),
+        ("mips64-openwrt-linux-musl", "mips64", "mips64", "musl", "/usr/lib64"),
+        ("mips64-unknown-linux-gnuabi64", "mips64", "mips64", "gnu", "/usr/lib64"),
+        ("mips64-unknown-linux-muslabi64", "mips64", "mips64", "musl", "/usr/lib64"),
+        ("mips64el-unknown-linux-gnuabi64", "mips64el", "mips64", "gnu", "/usr/lib64"),
+        ("mips64el-unknown-linux-muslabi64", "mips64el", "mips64", "musl", "/usr/lib64"),
+        ("mipsel-unknown-linux-gnu", "mipsel", "mips", "gnu", "/usr/lib"),
+        ("mipsel-unknown-linux-musl", "mipsel", "mips", "musl", "/usr/lib"),
+        ("mipsel-unknown-linux-uclibc", "mipsel", "mips", "uclibc", "/usr/lib"),
+        ("mipsisa32r6-unknown-linux-gnu", "mipsr6", "mips32r6", "gnu", "/usr/lib"),
+        ("mipsisa32r6el-unknown-linux-gnu", "mipsr6el", "mips32r6", "gnu", "/usr/lib"),
+        ("mipsisa64r6-unknown-linux-gnuabi64", "mips64r6", "mips64r6", "gnu", "/usr/lib64"),
+        ("mipsisa64r6el-unknown-linux-gnuabi64", "mips64r6el", "mips64r6", "gnu", "/usr/lib64"),
+        ("powerpc-unknown-linux-gnu", "ppc", "powerpc", "gnu", "/usr/lib"),
+        ("powerpc-unknown-linux-gnuspe", "ppc", "powerpc", "gnu", "/usr/lib"),
+        ("powerpc-unknown-linux-musl", "ppc", "powerpc", "musl", "/usr/lib"),
+        ("powerpc64-unknown-linux-gnu", "ppc64", "powerpc64", "gnu", "/usr/lib64"),
+        ("powerpc64-unknown-linux-musl", "ppc64", "powerpc64", "musl", "/usr/lib64"),
+        ("powerpc64le-unknown-linux-gnu", "ppc64le", "powerpc64", "gnu", "/usr/lib64"),
+        ("powerpc64le-unknown-linux-musl", "ppc64le", "powerpc64", "musl", "/usr/lib64"),
+        ("riscv32gc-unknown-linux-gnu", "riscv32", "riscv32", "gnu", "/usr/lib"),
+        ("riscv32gc-unknown-linux-musl", "riscv32", "riscv32", "musl", "/usr/lib"),
+        ("riscv64a23-unknown-linux-gnu", "riscv64", "riscv64", "gnu", "/usr/lib64"),
+        ("riscv64gc-unknown-linux-gnu", "riscv64", "riscv64", "gnu", "/usr/lib64"),
+        ("riscv64gc-unknown-linux-musl", "riscv64", "riscv64", "musl", "/usr/lib64"),
+        ("s390x-unknown-linux-gnu", "s390x", "s390x", "gnu", "/usr/lib64"),
+        ("s390x-unknown-linux-musl", "s390x", "s390x", "musl", "/usr/lib64"),
+        ("sparc-unknown-linux-gnu", "sparc", "sparc", "gnu", "/usr/lib"),
+        ("sparc64-unknown-linux-gnu", "sparc64", "sparc64", "gnu", "/usr/lib64"),
+        ("thumbv7neon-unknown-linux-gnueabihf", "armhfp", "arm", "gnu", "/usr/lib"),
+        ("thumbv7neon-unknown-linux-musleabihf", "armhfp", "arm", "musl", "/usr/lib"),
+        ("x86_64-unikraft-linux-musl", "x86_64", "x86_64", "musl", "/usr/lib64"),
+        ("x86_64-unknown-linux-gnu", "x86_64", "x86_64", "gnu", "/usr/lib64"),
+        ("x86_64-unknown-linux-gnux32", "x86_64", "x86_64", "gnu", "/usr/libx32"),
+        ("x86_64-unknown-linux-musl", "x86_64", "x86_64", "musl", "/usr/lib64"),
+        ("x86_64-unknown-linux-none", "x86_64", "x86_64", "", "/usr/lib64"),
+        ("x86_64-unknown-linux-ohos", "x86_64", "x86_64", "ohos", "/usr/lib64"),
+    ];
+
+    /// Arches rustc targets that rpm has no name for, so they're used as they are, and soft
+    /// float arm which is packaged as `arm-nofp`
+    const UNKNOWN_TO_RPM: &[&str] = &["aarch64_be", "arm-nofp", "armeb", "csky", "hexagon"];
+
+    #[test]
+    fn linux_triples() {
+        for &(triple, rpm_arch, cfg_arch, env, libdir) in TRIPLES {
+            let triplet = Triplet::from_str(triple).unwrap();
+            assert_eq!(triplet.os, "linux", "{triple}");
+            assert_eq!(triplet.rpm_arch(), rpm_arch, "{triple}");
+            assert_eq!(triplet.cfg_arch(), cfg_arch, "{triple}");
+            assert_eq!(triplet.env(), env, "{triple}");
+            assert_eq!(triplet.libdir(), libdir, "{triple}");
+            assert_eq!(triplet.to_string(), triple);
+        }
+    }
+
+    #[test]
+    fn rpm_arches_are_known() {
+        for &(triple, rpm_arch, ..) in TRIPLES {
+            assert!(
+                RPM_ARCHES.contains(&rpm_arch) || UNKNOWN_TO_RPM.contains(&rpm_arch),
+                "{triple} maps to {rpm_arch}, which rpm doesn't know"
+            );
+        }
+    }
+
+    #[test]
+    fn parts() {
+        let triplet = Triplet::from_str("armv7-unknown-linux-gnueabihf").unwrap();
+        assert_eq!(triplet.arch, "armv7");
+        assert_eq!(triplet.vendor, "unknown");
+        assert_eq!(triplet.os, "linux");
+        assert_eq!(triplet.libc.as_deref(), Some("gnueabihf"));
+
+        let triplet = Triplet::from_str("x86_64-unknown-linux").unwrap();
+        assert_eq!(triplet.libc, None);
+        assert_eq!(triplet.env(), "");
+    }
+
+    #[test]
+    fn arm_without_abi() {
+        // without an abi suffix arm is assumed to be hard float, like Fedora's armhfp
+        let armv7 = Triplet::from_str("armv7-unknown-linux").unwrap();
+        assert_eq!(armv7.rpm_arch(), "armhfp");
+
+        let arm = Triplet::from_str("arm-unknown-linux").unwrap();
+        assert_eq!(arm.rpm_arch(), "armhfp");
+    }
+
+    #[test]
+    fn android() {
+        for (triple, cfg_arch) in [
+            ("aarch64-linux-android", "aarch64"),
+            ("arm-linux-androideabi", "arm"),
+            ("armv7-linux-androideabi", "arm"),
+            ("i686-linux-android", "x86"),
+            ("riscv64-linux-android", "riscv64"),
+            ("thumbv7neon-linux-androideabi", "arm"),
+            ("x86_64-linux-android", "x86_64"),
+        ] {
+            let triplet = Triplet::from_str(triple).unwrap();
+            assert_eq!(triplet.vendor, "unknown", "{triple}");
+            assert_eq!(triplet.os, "android", "{triple}");
+            assert_eq!(triplet.cfg_arch(), cfg_arch, "{triple}");
+            assert_eq!(triplet.env(), "", "{triple}");
+            assert_eq!(triplet.to_string(), triple);
+        }
+
+        let armv7 = Triplet::from_str("armv7-linux-androideabi").unwrap();
+        assert_eq!(armv7.rpm_arch(), "arm-nofp");
+    }
+
+    #[test]
+    fn other_os() {
+        let triplet = Triplet::from_str("x86_64-pc-windows-msvc").unwrap();
+        assert_eq!(triplet.vendor, "pc");
+        assert_eq!(triplet.os, "windows");
+        assert_eq!(triplet.env(), "");
+
+        let triplet = Triplet::from_str("aarch64-apple-darwin").unwrap();
+        assert_eq!(triplet.os, "darwin");
+        assert_eq!(triplet.libc, None);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Triplet::from_str("x86_64").is_err());
+        assert!(Triplet::from_str("x86_64-unknown").is_err());
+    }
+}